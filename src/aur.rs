@@ -0,0 +1,318 @@
+//! Client for the AUR RPC interface (v5).
+
+use futures::stream::{self, StreamExt, TryStreamExt};
+use reqwest::Url;
+use serde::{Deserialize, Serialize};
+
+use crate::db;
+use crate::error::{RaurError, Result};
+
+pub const AUR_URL: &str = "https://aur.archlinux.org";
+
+/// Longest request URL the AUR accepts before answering 414 URI Too Long.
+const MAX_URL_LEN: usize = 4400;
+
+/// Info requests sent at the same time; more would only trip the AUR's rate limit.
+pub const MAX_CONCURRENT_REQUESTS: usize = 4;
+
+#[derive(Debug, Deserialize)]
+pub struct AurResponse {
+    #[serde(rename = "type")]
+    pub kind: String,
+    #[serde(default)]
+    pub results: Vec<AurPackage>,
+    pub error: Option<String>,
+}
+
+/// A package record as returned by the RPC. Search results only carry the
+/// basic fields; the dependency arrays are filled in by `info` queries.
+/// Serializes back to the RPC field names.
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+#[serde(rename_all = "PascalCase", default)]
+pub struct AurPackage {
+    #[serde(rename = "ID")]
+    pub id: u64,
+    pub name: String,
+    #[serde(rename = "PackageBaseID")]
+    pub package_base_id: u64,
+    pub package_base: String,
+    pub version: String,
+    pub description: Option<String>,
+    #[serde(rename = "URL")]
+    pub url: Option<String>,
+    #[serde(rename = "URLPath")]
+    pub url_path: Option<String>,
+    pub maintainer: Option<String>,
+    pub num_votes: u32,
+    pub popularity: f64,
+    /// Unix timestamp of when the package was flagged out of date
+    pub out_of_date: Option<i64>,
+    pub first_submitted: i64,
+    pub last_modified: i64,
+    pub depends: Vec<String>,
+    pub make_depends: Vec<String>,
+    pub check_depends: Vec<String>,
+    pub opt_depends: Vec<String>,
+    pub provides: Vec<String>,
+    pub conflicts: Vec<String>,
+    pub replaces: Vec<String>,
+    pub groups: Vec<String>,
+    pub keywords: Vec<String>,
+    pub license: Vec<String>,
+}
+
+impl AurPackage {
+    /// All dependencies needed to build and install the package.
+    pub fn build_dependencies(&self) -> impl Iterator<Item = &String> {
+        self.depends
+            .iter()
+            .chain(&self.make_depends)
+            .chain(&self.check_depends)
+    }
+
+    /// Whether this package satisfies `dep`, by name and version or by one
+    /// of its provides.
+    pub fn satisfies(&self, dep: &str) -> bool {
+        db::satisfies(&self.name, &self.version, &self.provides, dep)
+    }
+}
+
+/// Package field matched by a search (the RPC `by` parameter).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, clap::ValueEnum)]
+pub enum SearchBy {
+    Name,
+    #[default]
+    NameDesc,
+    Maintainer,
+    Depends,
+    Makedepends,
+    Provides,
+    Keywords,
+}
+
+impl SearchBy {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SearchBy::Name => "name",
+            SearchBy::NameDesc => "name-desc",
+            SearchBy::Maintainer => "maintainer",
+            SearchBy::Depends => "depends",
+            SearchBy::Makedepends => "makedepends",
+            SearchBy::Provides => "provides",
+            SearchBy::Keywords => "keywords",
+        }
+    }
+}
+
+impl std::fmt::Display for SearchBy {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct AurClient {
+    base_url: String,
+    http: reqwest::Client,
+}
+
+impl Default for AurClient {
+    fn default() -> Self {
+        AurClient::new()
+    }
+}
+
+impl AurClient {
+    pub fn new() -> Self {
+        AurClient::with_base_url(AUR_URL)
+    }
+
+    /// Talk to another aurweb instance, e.g. a local mock server in tests.
+    pub fn with_base_url(base_url: impl Into<String>) -> Self {
+        AurClient {
+            base_url: base_url.into().trim_end_matches('/').to_string(),
+            http: reqwest::Client::new(),
+        }
+    }
+
+    /// Git URL of a package base.
+    pub fn clone_url(&self, pkgbase: &str) -> String {
+        format!("{}/{}.git", self.base_url, pkgbase)
+    }
+
+    /// Web page of a package.
+    pub fn package_url(&self, pkgname: &str) -> String {
+        format!("{}/packages/{}", self.base_url, pkgname)
+    }
+
+    /// Search packages, matching `query` against the given field.
+    pub async fn search(&self, query: &str, by: SearchBy) -> Result<Vec<AurPackage>> {
+        let mut url = self.rpc_url()?;
+        url.query_pairs_mut()
+            .append_pair("type", "search")
+            .append_pair("by", by.as_str())
+            .append_pair("arg", query);
+
+        Ok(self.request(url).await?.results)
+    }
+
+    /// Fetch the full records of the given packages. Names that do not
+    /// exist in the AUR are simply missing from the result.
+    ///
+    /// Long lists are split into several requests, up to
+    /// [`MAX_CONCURRENT_REQUESTS`] of them in flight at once.
+    pub async fn info<S: AsRef<str>>(&self, names: &[S]) -> Result<Vec<AurPackage>> {
+        let responses: Vec<AurResponse> = stream::iter(self.info_urls(names)?)
+            .map(|url| self.request(url))
+            .buffered(MAX_CONCURRENT_REQUESTS)
+            .try_collect()
+            .await?;
+
+        Ok(responses.into_iter().flat_map(|resp| resp.results).collect())
+    }
+
+    fn rpc_url(&self) -> Result<Url> {
+        let mut url = Url::parse(&format!("{}/rpc/", self.base_url))
+            .map_err(|e| RaurError::Other(format!("invalid AUR URL '{}': {}", self.base_url, e)))?;
+        url.query_pairs_mut().append_pair("v", "5");
+        Ok(url)
+    }
+
+    /// Split an info query into as few requests as the URL length limit allows.
+    fn info_urls<S: AsRef<str>>(&self, names: &[S]) -> Result<Vec<Url>> {
+        let mut base = self.rpc_url()?;
+        base.query_pairs_mut().append_pair("type", "info");
+
+        let mut urls = Vec::new();
+        let mut current = base.clone();
+        let mut count = 0;
+        for name in names {
+            let mut next = current.clone();
+            next.query_pairs_mut().append_pair("arg[]", name.as_ref());
+
+            if next.as_str().len() > MAX_URL_LEN && count > 0 {
+                urls.push(current);
+                current = base.clone();
+                current.query_pairs_mut().append_pair("arg[]", name.as_ref());
+                count = 1;
+            } else {
+                current = next;
+                count += 1;
+            }
+        }
+        if count > 0 {
+            urls.push(current);
+        }
+
+        Ok(urls)
+    }
+
+    async fn request(&self, url: Url) -> Result<AurResponse> {
+        let resp = self
+            .http
+            .get(url)
+            .send()
+            .await?
+            .error_for_status()?
+            .json::<AurResponse>()
+            .await?;
+
+        if resp.kind == "error" {
+            let msg = resp.error.unwrap_or_else(|| "unknown error".into());
+            return Err(RaurError::Rpc(msg));
+        }
+
+        Ok(resp)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+
+    /// Answer a single HTTP request with `body` and hand back the request line.
+    async fn serve_once(body: &'static str) -> (String, tokio::task::JoinHandle<String>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let base_url = format!("http://{}", listener.local_addr().unwrap());
+
+        let handle = tokio::spawn(async move {
+            let (mut socket, _) = listener.accept().await.unwrap();
+            let mut buf = vec![0; 8192];
+            let n = socket.read(&mut buf).await.unwrap();
+            let request = String::from_utf8_lossy(&buf[..n]).to_string();
+
+            let response = format!(
+                "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                body.len(),
+                body
+            );
+            socket.write_all(response.as_bytes()).await.unwrap();
+            request.lines().next().unwrap_or_default().to_string()
+        });
+
+        (base_url, handle)
+    }
+
+    #[test]
+    fn info_arguments_are_encoded() {
+        let client = AurClient::with_base_url("https://aur.example.org/");
+        let urls = client.info_urls(&["foo", "c++ & friends"]).unwrap();
+
+        assert_eq!(urls.len(), 1);
+        assert_eq!(
+            urls[0].as_str(),
+            "https://aur.example.org/rpc/?v=5&type=info&arg%5B%5D=foo&arg%5B%5D=c%2B%2B+%26+friends"
+        );
+    }
+
+    #[test]
+    fn info_batches_respect_url_limit() {
+        let client = AurClient::new();
+        let names: Vec<String> = (0..1000).map(|i| format!("package-{:04}", i)).collect();
+        let urls = client.info_urls(&names).unwrap();
+
+        assert!(urls.len() > 1);
+        assert!(urls.iter().all(|url| url.as_str().len() <= MAX_URL_LEN));
+
+        let total: usize = urls.iter().map(|url| url.query_pairs().filter(|(k, _)| k == "arg[]").count()).sum();
+        assert_eq!(total, names.len());
+    }
+
+    #[tokio::test]
+    async fn info_deserializes_full_record() {
+        let (base_url, request) = serve_once(
+            r#"{"version":5,"type":"multiinfo","resultcount":1,"results":[{
+                "ID":1,"Name":"foo-git","PackageBaseID":2,"PackageBase":"foo",
+                "Version":"1:2.0.r12.gabc-3","Description":"Foo","URL":"https://foo.example",
+                "URLPath":"/cgit/aur.git/snapshot/foo.tar.gz","Maintainer":null,
+                "NumVotes":42,"Popularity":0.5,"OutOfDate":1700000000,
+                "FirstSubmitted":1500000000,"LastModified":1600000000,
+                "Depends":["bar>=1.0"],"MakeDepends":["git"],"CheckDepends":["baz"],
+                "License":["MIT"],"Keywords":["foo"]}]}"#,
+        )
+        .await;
+
+        let client = AurClient::with_base_url(base_url);
+        let packages = client.info(&["foo-git"]).await.unwrap();
+
+        assert_eq!(request.await.unwrap(), "GET /rpc/?v=5&type=info&arg%5B%5D=foo-git HTTP/1.1");
+        assert_eq!(packages.len(), 1);
+        let pkg = &packages[0];
+        assert_eq!(pkg.package_base, "foo");
+        assert_eq!(pkg.maintainer, None);
+        assert_eq!(pkg.out_of_date, Some(1700000000));
+        assert_eq!(pkg.build_dependencies().collect::<Vec<_>>(), ["bar>=1.0", "git", "baz"]);
+        assert_eq!(pkg.license, ["MIT"]);
+    }
+
+    #[tokio::test]
+    async fn rpc_errors_are_reported() {
+        let (base_url, _) = serve_once(r#"{"version":5,"type":"error","resultcount":0,"results":[],"error":"Too many package results."}"#).await;
+
+        let client = AurClient::with_base_url(base_url);
+        let err = client.search("a", SearchBy::NameDesc).await.unwrap_err();
+        assert_eq!(err.to_string(), "AUR RPC error: Too many package results.");
+    }
+}
@@ -0,0 +1,724 @@
+//! Installs and upgrades, AUR clones, makepkg builds and the clone cache.
+//!
+//! Clones live in the cache directory as `<cache>/<pkgbase>`, one per git
+//! repository on the AUR, so all split packages of a base share a clone and
+//! a build. The commit a base was last reviewed at is kept in
+//! `<cache>/.reviewed/<pkgbase>`, so later builds only need to show the diff
+//! since then.
+
+use std::collections::HashSet;
+use std::env;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::thread;
+use std::time::Duration;
+
+use indicatif::MultiProgress;
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+
+use crate::config::ReviewPolicy;
+use crate::error::{CommandFailure, RaurError, Result};
+use crate::escalation::Keepalive;
+use crate::pacman;
+use crate::resolve::{self, dep_name, AurBase};
+use crate::runner::Cmd;
+use crate::srcinfo::Srcinfo;
+use crate::ui::{self, InstallReport, UpgradeReport};
+use crate::Context;
+
+pub(crate) fn git(dir: &Path) -> Cmd {
+    Cmd::new("git").arg("-C").arg(dir)
+}
+
+// ======================
+// Install + upgrade
+// ======================
+/// Install the given names: whatever the sync databases know (packages,
+/// providers, groups) in one pacman transaction, the rest from the AUR.
+pub async fn install(ctx: &Context, packages: &[String], cascade: bool) -> Result<InstallReport> {
+    let targets = resolve::split_targets(ctx, packages)?;
+
+    if !targets.repo.is_empty() {
+        let repo_targets: Vec<String> = targets.repo.iter().map(|repo| repo.target.clone()).collect();
+        pacman::install(ctx, &repo_targets)?;
+    }
+
+    let aur = if targets.aur.is_empty() {
+        None
+    } else {
+        Some(install_aur(ctx, &targets.aur, cascade).await?)
+    };
+
+    Ok(InstallReport { repo: targets.repo, aur })
+}
+
+/// Refresh the sync databases and upgrade the repo packages, then rebuild
+/// the AUR packages that are out of date. `full` forces a download of the
+/// databases. Stops at the first step that fails.
+pub async fn upgrade(ctx: &Context, full: bool) -> Result<UpgradeReport> {
+    pacman::sync_databases(ctx, full)?;
+    pacman::upgrade(ctx)?;
+
+    let check = resolve::aur_upgrades(ctx).await?;
+    let installed = if check.outdated.is_empty() {
+        None
+    } else {
+        let targets: Vec<&str> = check.outdated.iter().map(|upgrade| upgrade.name.as_str()).collect();
+        Some(install_aur(ctx, &targets, false).await?)
+    };
+
+    Ok(UpgradeReport { aur: check, installed })
+}
+
+// ======================
+// AUR install (resolve + build + pacman -U)
+// ======================
+/// A package built (or found already built) in its clone.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct BuiltPackage {
+    pub name: String,
+    /// The base the package was built from, shared by split packages
+    pub pkgbase: String,
+    pub files: Vec<PathBuf>,
+    /// `false` when the package files were already there and makepkg was skipped
+    pub rebuilt: bool,
+    /// Lines of the base's `.SRCINFO` that were skipped, with file and line
+    pub srcinfo_warnings: Vec<String>,
+}
+
+/// What an AUR install did.
+#[derive(Debug, Default, Serialize)]
+pub struct AurInstall {
+    /// Dependencies installed from the official repos
+    pub repo_deps: Vec<String>,
+    /// Built and installed AUR packages, in build order
+    pub built: Vec<BuiltPackage>,
+    /// Dependencies that could not be marked as such and stay explicitly
+    /// installed
+    pub unmarked: Vec<String>,
+}
+
+/// Resolve the dependencies of the given AUR packages, build them in order
+/// and install the results with as few `pacman -U` transactions as possible.
+pub async fn install_aur<S: AsRef<str>>(ctx: &Context, targets: &[S], cascade: bool) -> Result<AurInstall> {
+    let targets: Vec<&str> = targets.iter().map(|t| t.as_ref()).collect();
+    let chain = build_chain(ctx, &targets, cascade, true).await?;
+
+    Ok(AurInstall {
+        repo_deps: chain.repo_deps,
+        built: chain.built,
+        unmarked: chain.unmarked,
+    })
+}
+
+/// What [`build_chain`] built and installed.
+struct Chain {
+    repo_deps: Vec<String>,
+    /// In build order
+    built: Vec<BuiltPackage>,
+    /// Built packages that were installed
+    installed: Vec<String>,
+    /// Installed dependencies `pacman -D --asdeps` failed on
+    unmarked: Vec<String>,
+}
+
+/// Resolve, fetch and build `targets` with their AUR dependencies.
+///
+/// With `install_all` every built package gets installed. Built packages are
+/// collected into one transaction that is only flushed early when a later
+/// build needs one of them. Otherwise only the packages a later build needs
+/// are installed, as dependencies.
+async fn build_chain(ctx: &Context, targets: &[&str], cascade: bool, install_all: bool) -> Result<Chain> {
+    refuse_root(ctx)?;
+    let resolution = resolve::resolve_dependencies(ctx, targets).await?;
+
+    if !resolution.missing.is_empty() {
+        return Err(RaurError::NotFound(resolution.missing));
+    }
+
+    let pkgbases: Vec<&str> = resolution.aur.iter().map(|base| base.pkgbase.as_str()).collect();
+    let clone_dirs = sync_clones(ctx, &pkgbases)?;
+
+    // Ask for the password now instead of after the builds
+    let names: Vec<&str> = resolution
+        .aur
+        .iter()
+        .flat_map(|base| base.packages.iter().map(|pkg| pkg.name.as_str()))
+        .collect();
+    let needs_root = install_all
+        || !resolution.repo.is_empty()
+        || resolution
+            .aur
+            .iter()
+            .any(|base| base.build_dependencies().any(|dep| names.contains(&dep_name(dep))));
+    let _keepalive = if needs_root { Some(Keepalive::start(ctx)?) } else { None };
+
+    if !resolution.repo.is_empty() {
+        pacman::install_deps(ctx, &resolution.repo)?;
+    }
+
+    let install_targets = if install_all { targets } else { &[] };
+    let mut built = Vec::new();
+    let mut installed = Vec::new();
+    let mut unmarked = Vec::new();
+    let mut pending: Vec<BuiltPackage> = Vec::new();
+    for (base, clone_dir) in resolution.aur.iter().zip(clone_dirs) {
+        let needed = |built: &BuiltPackage| base.build_dependencies().any(|dep| built.name == dep_name(dep));
+        if pending.iter().any(needed) {
+            let flush: Vec<BuiltPackage> = if install_all {
+                std::mem::take(&mut pending)
+            } else {
+                let (flush, keep) = std::mem::take(&mut pending).into_iter().partition(needed);
+                pending = keep;
+                flush
+            };
+            unmarked.extend(install_built(ctx, &flush, install_targets)?);
+            installed.extend(flush.into_iter().map(|pkg| pkg.name));
+        }
+
+        let wanted: Vec<&str> = base.packages.iter().map(|pkg| pkg.name.as_str()).collect();
+        let pkgs = build_clone(ctx, &base.pkgbase, &wanted, clone_dir, cascade)?;
+        built.extend(pkgs.iter().cloned());
+        pending.extend(pkgs);
+    }
+    if install_all {
+        unmarked.extend(install_built(ctx, &pending, install_targets)?);
+        installed.extend(pending.into_iter().map(|pkg| pkg.name));
+    }
+
+    Ok(Chain {
+        repo_deps: resolution.repo,
+        built,
+        installed,
+        unmarked,
+    })
+}
+
+/// Install built packages with a single `pacman -U`; everything that is not
+/// a target is marked as a dependency afterwards. Returns the dependencies
+/// that could not be marked.
+fn install_built(ctx: &Context, built: &[BuiltPackage], targets: &[&str]) -> Result<Vec<String>> {
+    if built.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let files: Vec<PathBuf> = built.iter().flat_map(|pkg| pkg.files.clone()).collect();
+    pacman::install_files(ctx, &files)?;
+
+    let deps: Vec<&str> = built
+        .iter()
+        .map(|pkg| pkg.name.as_str())
+        .filter(|name| !targets.contains(name))
+        .collect();
+    // Only the install reason is off, so a failure here is not fatal
+    if !deps.is_empty() && !pacman::mark_as_deps(ctx, &deps)? {
+        return Ok(deps.into_iter().map(String::from).collect());
+    }
+
+    Ok(Vec::new())
+}
+
+// ======================
+// AUR build (clone + makepkg)
+// ======================
+/// Clone, review and build the packages of an AUR base without installing
+/// them.
+pub fn build_package(ctx: &Context, base: &AurBase, cascade: bool) -> Result<Vec<BuiltPackage>> {
+    refuse_root(ctx)?;
+    let clone_dir = sync_clone(ctx, &base.pkgbase)?;
+    let wanted: Vec<&str> = base.packages.iter().map(|pkg| pkg.name.as_str()).collect();
+    build_clone(ctx, &base.pkgbase, &wanted, clone_dir, cascade)
+}
+
+/// Review and build an up to date clone. Returns the `wanted` split
+/// packages of the base, in the given order.
+fn build_clone(
+    ctx: &Context,
+    pkgbase: &str,
+    wanted: &[&str],
+    clone_dir: PathBuf,
+    cascade: bool,
+) -> Result<Vec<BuiltPackage>> {
+    let head = git_head(ctx, &clone_dir)?;
+
+    // The RPC may lag behind the clone, e.g. when a split package was dropped
+    let srcinfo_path = clone_dir.join(".SRCINFO");
+    let mut srcinfo_warnings = Vec::new();
+    if srcinfo_path.exists() {
+        let srcinfo = Srcinfo::from_file(&srcinfo_path)?;
+        if let Some(name) = wanted.iter().find(|name| srcinfo.package(name).is_none()) {
+            return Err(RaurError::Other(format!("'{}' is no longer built by '{}'", name, pkgbase)));
+        }
+        srcinfo_warnings = srcinfo
+            .warnings
+            .iter()
+            .map(|warning| format!("{}: {}", srcinfo_path.display(), warning))
+            .collect();
+    }
+
+    if !ui::review(ctx, pkgbase, &clone_dir, &head)? {
+        return Err(RaurError::UserAbort(format!("build of '{}' aborted", pkgbase)));
+    }
+    // A skipped review is not recorded, so the next reviewed build shows
+    // everything since the last real one
+    let record = |ctx: &Context| match ctx.config().review {
+        ReviewPolicy::Skip => Ok(()),
+        _ => record_reviewed(ctx, pkgbase, &head),
+    };
+
+    let all_files = package_files(ctx, &clone_dir)?;
+    let mut files: Vec<(&str, Vec<PathBuf>)> = wanted.iter().map(|name| (*name, Vec::new())).collect();
+    for file in all_files {
+        let name = file_package_name(&file)?;
+        if let Some((_, pkg_files)) = files.iter_mut().find(|(wanted, _)| *wanted == name) {
+            pkg_files.push(file);
+        }
+    }
+    if let Some((name, _)) = files.iter().find(|(_, pkg_files)| pkg_files.is_empty()) {
+        return Err(RaurError::Other(format!("'{}' does not build a package named '{}'", pkgbase, name)));
+    }
+    let built = |rebuilt: bool| -> Vec<BuiltPackage> {
+        files
+            .iter()
+            .map(|(name, pkg_files)| BuiltPackage {
+                name: name.to_string(),
+                pkgbase: pkgbase.to_string(),
+                files: pkg_files.clone(),
+                rebuilt,
+                srcinfo_warnings: srcinfo_warnings.clone(),
+            })
+            .collect()
+    };
+
+    if files.iter().flat_map(|(_, pkg_files)| pkg_files).all(|file| file.exists()) {
+        record(ctx)?;
+        return Ok(built(false));
+    }
+
+    // -f: a split package may have left some of its files from an earlier build
+    let makepkg_args = if cascade { "-sfc" } else { "-sf" };
+    if !ctx.config().noconfirm {
+        ctx.ensure_interactive("makepkg's questions")?;
+    }
+
+    let pb = ui::spinner(&format!("Building {}...", pkgbase));
+    let makepkg = Cmd::new("makepkg")
+        .arg(makepkg_args)
+        .args(pacman::noconfirm(ctx))
+        .current_dir(&clone_dir);
+    let status = ctx.runner().status(&makepkg)?;
+    pb.finish_and_clear();
+
+    if !status.success() {
+        return Err(RaurError::Build {
+            pkgbase: pkgbase.to_string(),
+            failure: CommandFailure::new(&makepkg, &status),
+        });
+    }
+
+    record(ctx)?;
+    Ok(built(true))
+}
+
+// ======================
+// Build only (resolve + build + copy)
+// ======================
+/// Name of the manifest written next to the package files.
+pub const MANIFEST: &str = "manifest.json";
+
+/// A package file copied to the output directory.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ManifestEntry {
+    pub name: String,
+    /// `[epoch:]pkgver-pkgrel`
+    pub version: String,
+    pub arch: String,
+    /// File name inside the output directory
+    pub file: String,
+    pub size: u64,
+    pub sha256: String,
+    /// `false` for dependencies built along with the requested packages
+    pub requested: bool,
+}
+
+/// What `raur build` did.
+#[derive(Debug, Serialize)]
+pub struct BuildOutput {
+    pub output: PathBuf,
+    /// Dependencies installed from the official repos
+    pub repo_deps: Vec<String>,
+    /// AUR packages installed because a later build needed them
+    pub installed: Vec<String>,
+    /// Of those, the ones that could not be marked as dependencies
+    pub unmarked: Vec<String>,
+    /// Skipped `.SRCINFO` lines of the built bases, see
+    /// [`BuiltPackage::srcinfo_warnings`]
+    pub srcinfo_warnings: Vec<String>,
+    /// Everything copied to `output`, also written to its [`MANIFEST`]
+    pub packages: Vec<ManifestEntry>,
+}
+
+/// Build the given AUR packages and their AUR dependencies without
+/// installing them, and copy all package files to `output`. Only what the
+/// builds themselves need gets installed.
+pub async fn build_aur<S: AsRef<str>>(ctx: &Context, targets: &[S], output: &Path) -> Result<BuildOutput> {
+    let targets: Vec<&str> = targets.iter().map(|t| t.as_ref()).collect();
+    let chain = build_chain(ctx, &targets, false, false).await?;
+
+    std::fs::create_dir_all(output)?;
+    let mut packages = Vec::new();
+    for pkg in &chain.built {
+        for file in &pkg.files {
+            let mut entry = manifest_entry(file)?;
+            entry.requested = targets.contains(&pkg.name.as_str());
+            std::fs::copy(file, output.join(&entry.file))?;
+            packages.push(entry);
+        }
+    }
+
+    #[derive(Serialize)]
+    struct Manifest<'a> {
+        packages: &'a [ManifestEntry],
+    }
+    let manifest = ui::Document::new("manifest", &Manifest { packages: &packages }).to_json();
+    std::fs::write(output.join(MANIFEST), manifest + "\n")?;
+
+    Ok(BuildOutput {
+        output: output.to_path_buf(),
+        repo_deps: chain.repo_deps,
+        installed: chain.installed,
+        unmarked: chain.unmarked,
+        srcinfo_warnings: srcinfo_warnings(&chain.built),
+        packages,
+    })
+}
+
+/// The `.SRCINFO` warnings of `built`, once per base.
+pub fn srcinfo_warnings(built: &[BuiltPackage]) -> Vec<String> {
+    let mut bases = HashSet::new();
+    built
+        .iter()
+        .filter(|pkg| bases.insert(pkg.pkgbase.as_str()))
+        .flat_map(|pkg| pkg.srcinfo_warnings.iter().cloned())
+        .collect()
+}
+
+/// Name, version and arch of a package file named like
+/// `<name>-<pkgver>-<pkgrel>-<arch>.pkg.tar.<ext>`.
+fn parse_file_name(file: &Path) -> Result<(String, String, String)> {
+    let file_name = file.file_name().unwrap_or_default().to_string_lossy();
+    let invalid = || RaurError::Other(format!("unexpected package file name '{}'", file_name));
+
+    let stem = file_name.split(".pkg.tar").next().ok_or_else(invalid)?;
+    let mut fields = stem.rsplitn(4, '-');
+    let (Some(arch), Some(pkgrel), Some(pkgver), Some(name)) = (fields.next(), fields.next(), fields.next(), fields.next())
+    else {
+        return Err(invalid());
+    };
+    Ok((name.to_string(), format!("{}-{}", pkgver, pkgrel), arch.to_string()))
+}
+
+fn file_package_name(file: &Path) -> Result<String> {
+    parse_file_name(file).map(|(name, _, _)| name)
+}
+
+/// Name, version and checksum of a package file.
+fn manifest_entry(file: &Path) -> Result<ManifestEntry> {
+    let file_name = file.file_name().unwrap_or_default().to_string_lossy().to_string();
+    let (name, version, arch) = parse_file_name(file)?;
+
+    let data = std::fs::read(file)?;
+    let sha256 = Sha256::digest(&data).iter().map(|byte| format!("{:02x}", byte)).collect();
+
+    Ok(ManifestEntry {
+        name,
+        version,
+        arch,
+        file: file_name,
+        size: data.len() as u64,
+        sha256,
+        requested: false,
+    })
+}
+
+/// makepkg refuses to run as root, and building untrusted PKGBUILDs as root
+/// is a bad idea anyway.
+fn refuse_root(ctx: &Context) -> Result<()> {
+    if ctx.runner().is_root() {
+        return Err(RaurError::Permission(
+            "refusing to build AUR packages as root, run raur as a regular user".into(),
+        ));
+    }
+    Ok(())
+}
+
+/// Paths of the package files a build produces (`makepkg --packagelist`).
+pub fn package_files(ctx: &Context, clone_dir: &Path) -> Result<Vec<PathBuf>> {
+    let cmd = Cmd::new("makepkg").arg("--packagelist").current_dir(clone_dir);
+    let output = ctx.runner().output(&cmd)?;
+    if !output.success() {
+        let pkgbase = clone_dir.file_name().unwrap_or_default().to_string_lossy().to_string();
+        return Err(RaurError::Build {
+            pkgbase,
+            failure: CommandFailure::new(&cmd, &output),
+        });
+    }
+
+    Ok(output
+        .stdout
+        .lines()
+        .map(PathBuf::from)
+        .collect())
+}
+
+/// [`sync_clone`] every package, up to `jobs` of them at once, with a
+/// progress line each. Returns the clone directories in the given order, or
+/// the error of the first package that failed.
+pub fn sync_clones(ctx: &Context, pkgbases: &[&str]) -> Result<Vec<PathBuf>> {
+    let progress = MultiProgress::new();
+    let bars: Vec<_> = pkgbases.iter().map(|name| ui::fetch_bar(&progress, name)).collect();
+    let results: Vec<Mutex<Option<Result<PathBuf>>>> = pkgbases.iter().map(|_| Mutex::new(None)).collect();
+    let next = AtomicUsize::new(0);
+
+    thread::scope(|scope| {
+        for _ in 0..ctx.config().jobs.min(pkgbases.len()) {
+            scope.spawn(|| loop {
+                let i = next.fetch_add(1, Ordering::Relaxed);
+                let Some(pkgbase) = pkgbases.get(i) else { break };
+
+                bars[i].set_message("fetching");
+                bars[i].enable_steady_tick(Duration::from_millis(100));
+                let result = sync_clone(ctx, pkgbase);
+                match &result {
+                    Ok(_) => bars[i].finish_with_message("done"),
+                    Err(_) => bars[i].abandon_with_message("failed"),
+                }
+                *results[i].lock().unwrap() = Some(result);
+            });
+        }
+    });
+
+    results
+        .into_iter()
+        .map(|result| result.into_inner().unwrap().expect("every package was fetched"))
+        .collect()
+}
+
+/// Clone the package base, or fetch and fast-forward an existing clone to
+/// the AUR's latest commit. A clone whose local changes, e.g. edits made
+/// during a review, keep it from fast-forwarding is left alone and reported
+/// as diverged. Returns the clone directory.
+pub fn sync_clone(ctx: &Context, pkgbase: &str) -> Result<PathBuf> {
+    let clone_dir = ctx.cache_dir()?.join(pkgbase);
+
+    let run = |cmd: Cmd| -> Result<()> {
+        // Captured, so parallel fetches do not garble the terminal
+        let status = ctx.runner().output(&cmd)?;
+        if !status.success() {
+            return Err(RaurError::Clone {
+                pkgbase: pkgbase.to_string(),
+                failure: CommandFailure::new(&cmd, &status),
+            });
+        }
+        Ok(())
+    };
+
+    if clone_dir.join(".git").is_dir() {
+        run(git(&clone_dir).args(["fetch", "--quiet", "origin"]))?;
+        let merge = git(&clone_dir).args(["merge", "--ff-only", "--quiet", "@{upstream}"]);
+        let status = ctx.runner().output(&merge)?;
+        if !status.success() {
+            return Err(RaurError::CloneDiverged {
+                pkgbase: pkgbase.to_string(),
+                clone_dir,
+                failure: CommandFailure::new(&merge, &status),
+            });
+        }
+        return Ok(clone_dir);
+    }
+
+    if clone_dir.exists() {
+        std::fs::remove_dir_all(&clone_dir)?;
+    }
+
+    run(Cmd::new("git").arg("clone").arg(ctx.aur().clone_url(pkgbase)).arg(&clone_dir))?;
+    Ok(clone_dir)
+}
+
+pub fn git_head(ctx: &Context, clone_dir: &Path) -> Result<String> {
+    let output = ctx.runner().output(&git(clone_dir).args(["rev-parse", "HEAD"]))?;
+    if !output.success() {
+        return Err(RaurError::Other(format!("could not read HEAD of {}", clone_dir.display())));
+    }
+    Ok(output.stdout.trim().to_string())
+}
+
+// ======================
+// PKGBUILD review
+// ======================
+/// How a clone relates to what the user reviewed before.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ReviewState {
+    /// HEAD is the reviewed commit
+    Unchanged,
+    /// HEAD moved on from the reviewed commit
+    Changed { since: String },
+    /// Never reviewed, or the reviewed commit is gone
+    New,
+}
+
+pub fn review_state(ctx: &Context, pkgbase: &str, clone_dir: &Path, head: &str) -> Result<ReviewState> {
+    Ok(match last_reviewed(ctx, pkgbase)? {
+        Some(commit) if commit == head => ReviewState::Unchanged,
+        Some(commit) if commit_exists(ctx, clone_dir, &commit)? => ReviewState::Changed { since: commit },
+        _ => ReviewState::New,
+    })
+}
+
+/// The PKGBUILD followed by any .install scripts, relative to the clone.
+pub fn build_files(clone_dir: &Path) -> Result<Vec<PathBuf>> {
+    let mut install_files = Vec::new();
+    for entry in std::fs::read_dir(clone_dir)? {
+        let path = PathBuf::from(entry?.file_name());
+        if path.extension().is_some_and(|ext| ext == "install") {
+            install_files.push(path);
+        }
+    }
+    install_files.sort();
+
+    let mut files = vec![PathBuf::from("PKGBUILD")];
+    files.extend(install_files);
+    Ok(files)
+}
+
+/// Open the build files in `$EDITOR`. Returns whether the editor exited cleanly.
+pub fn edit_build_files(ctx: &Context, clone_dir: &Path) -> Result<bool> {
+    let editor = env::var("EDITOR").unwrap_or_else(|_| "vi".to_string());
+    let mut words = editor.split_whitespace();
+    let program = words.next().unwrap_or("vi");
+
+    let status = ctx.runner().status(
+        &Cmd::new(program)
+            .args(words)
+            .args(build_files(clone_dir)?)
+            .current_dir(clone_dir),
+    )?;
+    Ok(status.success())
+}
+
+fn commit_exists(ctx: &Context, clone_dir: &Path, commit: &str) -> Result<bool> {
+    let output = ctx.runner().output(&git(clone_dir).args(["cat-file", "-e", &format!("{}^{{commit}}", commit)]))?;
+    Ok(output.success())
+}
+
+pub fn last_reviewed(ctx: &Context, pkgbase: &str) -> Result<Option<String>> {
+    match std::fs::read_to_string(ctx.cache_dir()?.join(".reviewed").join(pkgbase)) {
+        Ok(commit) => Ok(Some(commit.trim().to_string())),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(e.into()),
+    }
+}
+
+pub fn record_reviewed(ctx: &Context, pkgbase: &str, commit: &str) -> Result<()> {
+    let dir = ctx.cache_dir()?.join(".reviewed");
+    std::fs::create_dir_all(&dir)?;
+    std::fs::write(dir.join(pkgbase), format!("{}\n", commit))?;
+    Ok(())
+}
+
+// ======================
+// Clean cache
+// ======================
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, clap::ValueEnum)]
+#[serde(rename_all = "kebab-case")]
+pub enum CleanPolicy {
+    /// Remove every clone
+    All,
+    /// Remove clones of packages that are not installed
+    KeepInstalled,
+    /// Keep the clones, remove untracked build artifacts
+    Artifacts,
+}
+
+/// Clones touched by a clean.
+#[derive(Debug, Default, Serialize)]
+pub struct CleanReport {
+    /// Clones that were removed or cleaned
+    pub cleaned: Vec<String>,
+    /// Clones `git clean` failed on
+    pub failed: Vec<String>,
+}
+
+pub fn clean(ctx: &Context, policy: CleanPolicy) -> Result<CleanReport> {
+    let cache_dir = ctx.cache_dir()?;
+
+    let mut clones = Vec::new();
+    for entry in std::fs::read_dir(&cache_dir)? {
+        let entry = entry?;
+        let name = entry.file_name().to_string_lossy().to_string();
+        if entry.file_type()?.is_dir() && !name.starts_with('.') {
+            clones.push(name);
+        }
+    }
+    clones.sort();
+
+    let mut report = CleanReport::default();
+    match policy {
+        CleanPolicy::All => {
+            for name in clones {
+                std::fs::remove_dir_all(cache_dir.join(&name))?;
+                report.cleaned.push(name);
+            }
+            let reviewed = cache_dir.join(".reviewed");
+            if reviewed.exists() {
+                std::fs::remove_dir_all(reviewed)?;
+            }
+        }
+        CleanPolicy::KeepInstalled => {
+            // A clone of a split package base is kept while any of its
+            // packages is installed
+            let packages: Vec<Vec<String>> = clones
+                .iter()
+                .map(|pkgbase| clone_packages(&cache_dir.join(pkgbase), pkgbase))
+                .collect();
+            let installed = pacman::installed(ctx, packages.iter().flatten().map(|name| name.as_str()))?;
+            let unused: Vec<String> = clones
+                .into_iter()
+                .zip(packages)
+                .filter(|(_, packages)| !packages.iter().any(|name| installed.contains(name)))
+                .map(|(pkgbase, _)| pkgbase)
+                .collect();
+            for name in unused {
+                std::fs::remove_dir_all(cache_dir.join(&name))?;
+                let record = cache_dir.join(".reviewed").join(&name);
+                if record.exists() {
+                    std::fs::remove_file(record)?;
+                }
+                report.cleaned.push(name);
+            }
+        }
+        CleanPolicy::Artifacts => {
+            for name in clones {
+                let status = ctx.runner().status(&git(&cache_dir.join(&name)).args(["clean", "-ffdx", "--quiet"]))?;
+                if status.success() {
+                    report.cleaned.push(name);
+                } else {
+                    report.failed.push(name);
+                }
+            }
+        }
+    }
+
+    Ok(report)
+}
+
+/// The packages a clone builds according to its .SRCINFO, or just the
+/// clone's name when there is none or it does not parse.
+fn clone_packages(clone_dir: &Path, pkgbase: &str) -> Vec<String> {
+    match Srcinfo::from_file(clone_dir) {
+        Ok(srcinfo) => srcinfo.packages.into_iter().map(|pkg| pkg.pkgname).collect(),
+        Err(_) => vec![pkgbase.to_string()],
+    }
+}
@@ -0,0 +1,545 @@
+//! Settings layered from, in order of precedence (last wins):
+//!
+//! 1. built-in defaults
+//! 2. the system config, `/etc/raur.conf`
+//! 3. the user config, `$XDG_CONFIG_HOME/raur/config.toml` (usually `~/.config/raur/config.toml`)
+//! 4. `RAUR_*` environment variables, e.g. `RAUR_SEARCH_LIMIT=20`
+//! 5. command line flags
+//!
+//! Both files are flat TOML:
+//!
+//! ```toml
+//! cache_dir = "~/.cache/raur"
+//! aur_url = "https://aur.archlinux.org"
+//! pacman_conf = "/etc/pacman.conf"
+//! dbpath = "/var/lib/pacman"
+//! noconfirm = true
+//! search_limit = 10
+//! jobs = 4
+//! escalation = "sudo"
+//! review = "ask"
+//! provider = "ask"
+//! remove = "ask"
+//! ```
+//!
+//! `dbpath` is only needed to override the `DBPath` of `pacman_conf`.
+//!
+//! `review`, `provider` and `remove` decide how raur's own questions are
+//! answered, see [`ReviewPolicy`], [`ProviderPolicy`] and [`RemovePolicy`].
+
+use std::collections::BTreeMap;
+use std::env;
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+use clap::ValueEnum;
+use serde::Serialize;
+use toml_edit::{DocumentMut, Item, Value};
+
+use crate::aur::AUR_URL;
+use crate::error::{RaurError, Result};
+use crate::escalation::Escalation;
+use crate::pacman_conf::PACMAN_CONF;
+
+pub const SYSTEM_CONFIG: &str = "/etc/raur.conf";
+
+/// All settings, in the order `raur config list` shows them.
+pub const KEYS: [&str; 11] = [
+    "cache_dir",
+    "aur_url",
+    "pacman_conf",
+    "dbpath",
+    "noconfirm",
+    "search_limit",
+    "jobs",
+    "escalation",
+    "review",
+    "provider",
+    "remove",
+];
+
+// ======================
+// Answer policies
+// ======================
+/// What happens to the build files of an AUR package before it is built.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, ValueEnum)]
+#[serde(rename_all = "kebab-case")]
+pub enum ReviewPolicy {
+    /// Show the diff or the files and ask whether to build
+    Ask,
+    /// Show the diff or the files and build
+    Show,
+    /// Build without showing anything
+    Skip,
+}
+
+/// Which package is installed when several repo packages provide a name.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, ValueEnum)]
+#[serde(rename_all = "kebab-case")]
+pub enum ProviderPolicy {
+    /// List the providers and ask
+    Ask,
+    /// Take the first provider, as pacman does by default
+    First,
+}
+
+/// The answer to "Are you sure you want to remove ...?".
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, ValueEnum)]
+#[serde(rename_all = "kebab-case")]
+pub enum RemovePolicy {
+    Ask,
+    Yes,
+    No,
+}
+
+fn parse_choice<T: ValueEnum>(value: &str) -> Result<T, String> {
+    T::from_str(value, true).map_err(|_| {
+        let names: Vec<String> = T::value_variants().iter().map(choice_name).collect();
+        format!("expected one of {}", names.join(", "))
+    })
+}
+
+fn choice_name<T: ValueEnum>(value: &T) -> String {
+    value
+        .to_possible_value()
+        .map(|value| value.get_name().to_string())
+        .unwrap_or_default()
+}
+
+/// Where the value of a setting came from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Source {
+    Default,
+    System,
+    User,
+    Env,
+    Cli,
+}
+
+impl fmt::Display for Source {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Source::Default => "default",
+            Source::System => "system",
+            Source::User => "user",
+            Source::Env => "env",
+            Source::Cli => "cli",
+        })
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Config {
+    /// Where AUR clones are kept; `None` when neither `XDG_CACHE_HOME` nor
+    /// `HOME` is set and nothing was configured
+    pub cache_dir: Option<PathBuf>,
+    pub aur_url: String,
+    /// pacman's configuration, for its repos, paths and ignored packages
+    pub pacman_conf: PathBuf,
+    /// pacman's database directory; `None` takes the one of `pacman_conf`
+    pub dbpath: Option<PathBuf>,
+    /// Pass `--noconfirm` to pacman and makepkg
+    pub noconfirm: bool,
+    /// Search results shown per source, 0 shows all
+    pub search_limit: usize,
+    /// AUR clones fetched at the same time
+    pub jobs: usize,
+    /// Backend that runs pacman as root
+    pub escalation: Escalation,
+    pub review: ReviewPolicy,
+    pub provider: ProviderPolicy,
+    pub remove: RemovePolicy,
+    sources: BTreeMap<&'static str, Source>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            cache_dir: default_cache_dir(),
+            aur_url: AUR_URL.to_string(),
+            pacman_conf: PathBuf::from(PACMAN_CONF),
+            dbpath: None,
+            noconfirm: true,
+            search_limit: 10,
+            jobs: 4,
+            escalation: Escalation::Sudo,
+            review: ReviewPolicy::Ask,
+            provider: ProviderPolicy::Ask,
+            remove: RemovePolicy::Ask,
+            sources: BTreeMap::new(),
+        }
+    }
+}
+
+impl Config {
+    /// Load the system and user config files and the environment.
+    pub fn load() -> Result<Config> {
+        Config::load_from(Some(Path::new(SYSTEM_CONFIG)), user_config_path().as_deref(), env::vars())
+    }
+
+    /// Load the given config files (missing ones are skipped) and `RAUR_*`
+    /// variables from `vars`.
+    pub fn load_from(
+        system: Option<&Path>,
+        user: Option<&Path>,
+        vars: impl IntoIterator<Item = (String, String)>,
+    ) -> Result<Config> {
+        let mut config = Config::default();
+        if let Some(path) = system {
+            config.merge_file(path, Source::System)?;
+        }
+        if let Some(path) = user {
+            config.merge_file(path, Source::User)?;
+        }
+
+        // Unknown RAUR_* variables are left alone, they may belong to something else
+        for (name, value) in vars {
+            let Some(key) = name.strip_prefix("RAUR_").map(str::to_lowercase) else { continue };
+            if KEYS.contains(&key.as_str()) {
+                config
+                    .set(&key, &value, Source::Env)
+                    .map_err(|e| RaurError::Config(format!("{}: {}", name, e)))?;
+            }
+        }
+
+        Ok(config)
+    }
+
+    fn merge_file(&mut self, path: &Path, source: Source) -> Result<()> {
+        let text = match std::fs::read_to_string(path) {
+            Ok(text) => text,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(()),
+            Err(e) => return Err(RaurError::Config(format!("{}: {}", path.display(), e))),
+        };
+        let doc: DocumentMut = text
+            .parse()
+            .map_err(|e| RaurError::Config(format!("{}: {}", path.display(), e)))?;
+
+        for (key, item) in doc.iter() {
+            let value = match item.as_value() {
+                Some(Value::String(s)) => s.value().clone(),
+                Some(Value::Integer(i)) => i.value().to_string(),
+                Some(Value::Boolean(b)) => b.value().to_string(),
+                _ => {
+                    return Err(RaurError::Config(format!(
+                        "{}: '{}' must be a string, number or boolean",
+                        path.display(),
+                        key
+                    )))
+                }
+            };
+            self.set(key, &value, source)
+                .map_err(|e| RaurError::Config(format!("{}: {}", path.display(), e)))?;
+        }
+
+        Ok(())
+    }
+
+    /// Validate and set one setting from its text form.
+    pub fn set(&mut self, key: &str, value: &str, source: Source) -> Result<()> {
+        let invalid = |expected: &str| RaurError::Config(format!("invalid {} '{}': {}", key, value, expected));
+
+        let key = match key {
+            "cache_dir" => {
+                let path = expand_home(value);
+                if !path.is_absolute() {
+                    return Err(invalid("expected an absolute path"));
+                }
+                self.cache_dir = Some(path);
+                "cache_dir"
+            }
+            "aur_url" => {
+                match reqwest::Url::parse(value) {
+                    Ok(url) if matches!(url.scheme(), "http" | "https") => {}
+                    _ => return Err(invalid("expected an http(s) URL")),
+                }
+                self.aur_url = value.trim_end_matches('/').to_string();
+                "aur_url"
+            }
+            "pacman_conf" => {
+                let path = expand_home(value);
+                if !path.is_absolute() {
+                    return Err(invalid("expected an absolute path"));
+                }
+                self.pacman_conf = path;
+                "pacman_conf"
+            }
+            "dbpath" => {
+                let path = expand_home(value);
+                if !path.is_absolute() {
+                    return Err(invalid("expected an absolute path"));
+                }
+                self.dbpath = Some(path);
+                "dbpath"
+            }
+            "noconfirm" => {
+                self.noconfirm = match value.to_lowercase().as_str() {
+                    "true" | "yes" | "1" => true,
+                    "false" | "no" | "0" => false,
+                    _ => return Err(invalid("expected true or false")),
+                };
+                "noconfirm"
+            }
+            "search_limit" => {
+                self.search_limit = value.parse().map_err(|_| invalid("expected a number, 0 for no limit"))?;
+                "search_limit"
+            }
+            "jobs" => {
+                self.jobs = match value.parse() {
+                    Ok(jobs) if jobs > 0 => jobs,
+                    _ => return Err(invalid("expected a number of at least 1")),
+                };
+                "jobs"
+            }
+            "escalation" => {
+                self.escalation = value.parse().map_err(|e: String| invalid(&e))?;
+                "escalation"
+            }
+            "review" => {
+                self.review = parse_choice(value).map_err(|e| invalid(&e))?;
+                "review"
+            }
+            "provider" => {
+                self.provider = parse_choice(value).map_err(|e| invalid(&e))?;
+                "provider"
+            }
+            "remove" => {
+                self.remove = parse_choice(value).map_err(|e| invalid(&e))?;
+                "remove"
+            }
+            _ => {
+                return Err(RaurError::Config(format!(
+                    "unknown setting '{}' (known: {})",
+                    key,
+                    KEYS.join(", ")
+                )))
+            }
+        };
+
+        self.sources.insert(key, source);
+        Ok(())
+    }
+
+    /// The text form of a setting.
+    pub fn get(&self, key: &str) -> Result<String> {
+        Ok(match key {
+            "cache_dir" => self.cache_dir.as_ref().map(|dir| dir.display().to_string()).unwrap_or_default(),
+            "aur_url" => self.aur_url.clone(),
+            "pacman_conf" => self.pacman_conf.display().to_string(),
+            "dbpath" => self.dbpath.as_ref().map(|dir| dir.display().to_string()).unwrap_or_default(),
+            "noconfirm" => self.noconfirm.to_string(),
+            "search_limit" => self.search_limit.to_string(),
+            "jobs" => self.jobs.to_string(),
+            "escalation" => self.escalation.to_string(),
+            "review" => choice_name(&self.review),
+            "provider" => choice_name(&self.provider),
+            "remove" => choice_name(&self.remove),
+            _ => {
+                return Err(RaurError::Config(format!(
+                    "unknown setting '{}' (known: {})",
+                    key,
+                    KEYS.join(", ")
+                )))
+            }
+        })
+    }
+
+    /// `--noconfirm`: never ask. pacman and makepkg get `--noconfirm`, and
+    /// questions left at `ask` get the answer an unattended run needs.
+    pub fn unattended(&mut self) {
+        self.noconfirm = true;
+        self.sources.insert("noconfirm", Source::Cli);
+        if self.review == ReviewPolicy::Ask {
+            self.review = ReviewPolicy::Skip;
+            self.sources.insert("review", Source::Cli);
+        }
+        if self.provider == ProviderPolicy::Ask {
+            self.provider = ProviderPolicy::First;
+            self.sources.insert("provider", Source::Cli);
+        }
+        if self.remove == RemovePolicy::Ask {
+            self.remove = RemovePolicy::Yes;
+            self.sources.insert("remove", Source::Cli);
+        }
+    }
+
+    /// `--confirm`: ask every question, including pacman's and makepkg's.
+    pub fn confirm_all(&mut self) {
+        self.noconfirm = false;
+        self.review = ReviewPolicy::Ask;
+        self.provider = ProviderPolicy::Ask;
+        self.remove = RemovePolicy::Ask;
+        for key in ["noconfirm", "review", "provider", "remove"] {
+            self.sources.insert(key, Source::Cli);
+        }
+    }
+
+    pub fn source(&self, key: &str) -> Source {
+        self.sources.get(key).copied().unwrap_or(Source::Default)
+    }
+
+    pub fn cache_dir(&self) -> Result<PathBuf> {
+        self.cache_dir.clone().ok_or_else(|| {
+            RaurError::Config("no cache directory: HOME is not set, set cache_dir in the config".into())
+        })
+    }
+}
+
+/// Write `key = value` into a config file, keeping the rest of the file and
+/// its comments. The value is validated first.
+pub fn write_setting(path: &Path, key: &str, value: &str) -> Result<()> {
+    Config::default().set(key, value, Source::Cli)?;
+
+    let text = match std::fs::read_to_string(path) {
+        Ok(text) => text,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => String::new(),
+        Err(e) => return Err(e.into()),
+    };
+    let mut doc: DocumentMut = text
+        .parse()
+        .map_err(|e| RaurError::Config(format!("{}: {}", path.display(), e)))?;
+
+    doc[key] = match key {
+        "noconfirm" => Item::from(matches!(value.to_lowercase().as_str(), "true" | "yes" | "1")),
+        "search_limit" | "jobs" => Item::from(value.parse::<i64>().unwrap_or_default()),
+        _ => Item::from(value),
+    };
+
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    std::fs::write(path, doc.to_string()).map_err(|e| match e.kind() {
+        std::io::ErrorKind::PermissionDenied => RaurError::Permission(format!("cannot write {}: {}", path.display(), e)),
+        _ => e.into(),
+    })
+}
+
+pub fn user_config_path() -> Option<PathBuf> {
+    xdg_dir("XDG_CONFIG_HOME", ".config").map(|dir| dir.join("raur/config.toml"))
+}
+
+fn default_cache_dir() -> Option<PathBuf> {
+    xdg_dir("XDG_CACHE_HOME", ".cache").map(|dir| dir.join("raur"))
+}
+
+/// An XDG base directory, falling back to `$HOME/<fallback>`.
+fn xdg_dir(var: &str, fallback: &str) -> Option<PathBuf> {
+    match env::var_os(var).map(PathBuf::from) {
+        Some(dir) if dir.is_absolute() => Some(dir),
+        _ => env::var_os("HOME").map(|home| PathBuf::from(home).join(fallback)),
+    }
+}
+
+fn expand_home(value: &str) -> PathBuf {
+    match (value.strip_prefix("~/"), env::var_os("HOME")) {
+        (Some(rest), Some(home)) => PathBuf::from(home).join(rest),
+        _ => PathBuf::from(value),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn vars(pairs: &[(&str, &str)]) -> Vec<(String, String)> {
+        pairs.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect()
+    }
+
+    #[test]
+    fn later_layers_win() {
+        let dir = tempfile::tempdir().unwrap();
+        let system = dir.path().join("raur.conf");
+        let user = dir.path().join("config.toml");
+        std::fs::write(&system, "search_limit = 20\nescalation = \"doas\"\naur_url = \"https://aur.example.org/\"\n").unwrap();
+        std::fs::write(&user, "# mine\nsearch_limit = 30\nnoconfirm = false\n").unwrap();
+
+        let config = Config::load_from(
+            Some(&system),
+            Some(&user),
+            vars(&[("RAUR_NOCONFIRM", "yes"), ("RAUR_UNRELATED", "x"), ("PATH", "/bin")]),
+        )
+        .unwrap();
+
+        assert_eq!(config.search_limit, 30);
+        assert_eq!(config.source("search_limit"), Source::User);
+        assert_eq!(config.escalation, Escalation::Doas);
+        assert_eq!(config.source("escalation"), Source::System);
+        assert_eq!(config.aur_url, "https://aur.example.org");
+        assert!(config.noconfirm);
+        assert_eq!(config.source("noconfirm"), Source::Env);
+        assert_eq!(config.source("cache_dir"), Source::Default);
+    }
+
+    #[test]
+    fn missing_files_are_skipped() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = Config::load_from(Some(&dir.path().join("nope")), None, vec![]).unwrap();
+        assert_eq!(config.search_limit, 10);
+    }
+
+    #[test]
+    fn invalid_values_name_their_origin() {
+        let dir = tempfile::tempdir().unwrap();
+        let user = dir.path().join("config.toml");
+
+        std::fs::write(&user, "search_limit = \"ten\"\n").unwrap();
+        let err = Config::load_from(None, Some(&user), vec![]).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            format!("{}: invalid search_limit 'ten': expected a number, 0 for no limit", user.display())
+        );
+
+        std::fs::write(&user, "colour = true\n").unwrap();
+        let err = Config::load_from(None, Some(&user), vec![]).unwrap_err();
+        assert!(err.to_string().ends_with("unknown setting 'colour' (known: cache_dir, aur_url, pacman_conf, dbpath, noconfirm, search_limit, jobs, escalation, review, provider, remove)"));
+
+        std::fs::write(&user, "search_limit = \n").unwrap();
+        assert!(Config::load_from(None, Some(&user), vec![]).is_err());
+
+        let err = Config::load_from(None, None, vars(&[("RAUR_AUR_URL", "aur.archlinux.org")])).unwrap_err();
+        assert_eq!(err.to_string(), "RAUR_AUR_URL: invalid aur_url 'aur.archlinux.org': expected an http(s) URL");
+        assert_eq!(err.exit_code(), 11);
+
+        let err = Config::default().set("escalation", "su", Source::Cli).unwrap_err();
+        assert_eq!(err.to_string(), "invalid escalation 'su': expected one of sudo, doas, run0, pkexec");
+
+        let err = Config::default().set("cache_dir", "relative/dir", Source::Cli).unwrap_err();
+        assert_eq!(err.to_string(), "invalid cache_dir 'relative/dir': expected an absolute path");
+    }
+
+    #[test]
+    fn noconfirm_only_answers_open_questions() {
+        let mut config = Config::load_from(None, None, vars(&[("RAUR_REMOVE", "no"), ("RAUR_REVIEW", "Show")])).unwrap();
+        assert_eq!(config.get("review").unwrap(), "show");
+
+        config.unattended();
+        assert_eq!(config.review, ReviewPolicy::Show);
+        assert_eq!(config.provider, ProviderPolicy::First);
+        assert_eq!(config.remove, RemovePolicy::No);
+        assert_eq!(config.source("remove"), Source::Env);
+
+        config.confirm_all();
+        assert!(!config.noconfirm);
+        assert_eq!(config.remove, RemovePolicy::Ask);
+
+        let err = config.set("provider", "last", Source::User).unwrap_err();
+        assert_eq!(err.to_string(), "invalid provider 'last': expected one of ask, first");
+    }
+
+    #[test]
+    fn write_setting_keeps_comments() {
+        let dir = tempfile::tempdir().unwrap();
+        let user = dir.path().join("raur/config.toml");
+
+        write_setting(&user, "search_limit", "25").unwrap();
+        std::fs::write(&user, format!("# tuned for CI\n{}", std::fs::read_to_string(&user).unwrap())).unwrap();
+        write_setting(&user, "noconfirm", "no").unwrap();
+
+        assert_eq!(
+            std::fs::read_to_string(&user).unwrap(),
+            "# tuned for CI\nsearch_limit = 25\nnoconfirm = false\n"
+        );
+        assert!(write_setting(&user, "search_limit", "-1").is_err());
+    }
+}
@@ -0,0 +1,580 @@
+//! pacman's databases, read directly instead of through pacman's output.
+//!
+//! The local database is a directory per installed package,
+//! `<dbpath>/local/<name>-<version>/desc`. Each sync database is an archive,
+//! `<dbpath>/sync/<repo>.db`, holding the same layout as a tar file
+//! compressed with gzip or zstd. Both use the `%KEY%` format libalpm writes:
+//! a `%KEY%` line followed by one value per line, ended by a blank line.
+
+use std::collections::{BTreeMap, HashMap};
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+use regex::RegexBuilder;
+use serde::Serialize;
+
+use crate::error::{RaurError, Result};
+use crate::pacman::RepoInfo;
+use crate::resolve::dep_name;
+use crate::version::vercmp;
+use crate::Context;
+
+/// pacman's default `DBPath`.
+pub const DB_PATH: &str = "/var/lib/pacman";
+
+// ======================
+// desc files
+// ======================
+/// Split a `desc` file into its `%KEY%` sections. Unknown keys are kept,
+/// lines outside a section are ignored.
+pub fn parse_desc(text: &str) -> HashMap<String, Vec<String>> {
+    let mut fields: HashMap<String, Vec<String>> = HashMap::new();
+    let mut key: Option<String> = None;
+
+    for line in text.lines() {
+        if line.is_empty() {
+            key = None;
+        } else if let Some(name) = line.strip_prefix('%').and_then(|l| l.strip_suffix('%')).filter(|_| key.is_none()) {
+            fields.entry(name.to_string()).or_default();
+            key = Some(name.to_string());
+        } else if let Some(key) = &key {
+            fields.get_mut(key).expect("section was created").push(line.to_string());
+        }
+    }
+
+    fields
+}
+
+/// Why a package is installed (`%REASON%`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum InstallReason {
+    Explicit,
+    Dependency,
+}
+
+/// An installed package as recorded in the local database.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct LocalPackage {
+    pub name: String,
+    pub version: String,
+    /// Entries written by old pacman versions lack it
+    pub base: Option<String>,
+    pub description: String,
+    pub url: String,
+    pub arch: String,
+    /// Unix timestamps
+    pub build_date: Option<i64>,
+    pub install_date: Option<i64>,
+    pub packager: String,
+    /// Installed size in bytes
+    pub size: u64,
+    pub reason: InstallReason,
+    pub licenses: Vec<String>,
+    /// How the package was checked before install: none, md5, sha256, pgp
+    pub validation: Vec<String>,
+    pub groups: Vec<String>,
+    pub depends: Vec<String>,
+    /// `name: reason` entries
+    pub optdepends: Vec<String>,
+    pub conflicts: Vec<String>,
+    pub provides: Vec<String>,
+    pub replaces: Vec<String>,
+}
+
+impl LocalPackage {
+    fn from_desc(text: &str) -> Option<LocalPackage> {
+        let mut fields = parse_desc(text);
+        let mut list = |key: &str| fields.remove(key).unwrap_or_default();
+        let first = |values: Vec<String>| values.into_iter().next();
+
+        Some(LocalPackage {
+            name: first(list("NAME"))?,
+            version: first(list("VERSION"))?,
+            base: first(list("BASE")),
+            description: first(list("DESC")).unwrap_or_default(),
+            url: first(list("URL")).unwrap_or_default(),
+            arch: first(list("ARCH")).unwrap_or_default(),
+            build_date: first(list("BUILDDATE")).and_then(|date| date.parse().ok()),
+            install_date: first(list("INSTALLDATE")).and_then(|date| date.parse().ok()),
+            packager: first(list("PACKAGER")).unwrap_or_default(),
+            size: first(list("SIZE")).and_then(|size| size.parse().ok()).unwrap_or(0),
+            // libalpm leaves the section out for explicitly installed packages
+            reason: match first(list("REASON")).as_deref() {
+                Some("1") => InstallReason::Dependency,
+                _ => InstallReason::Explicit,
+            },
+            licenses: list("LICENSE"),
+            validation: list("VALIDATION"),
+            groups: list("GROUPS"),
+            depends: list("DEPENDS"),
+            optdepends: list("OPTDEPENDS"),
+            conflicts: list("CONFLICTS"),
+            provides: list("PROVIDES"),
+            replaces: list("REPLACES"),
+        })
+    }
+
+    /// Whether this package satisfies `dep`, see [`satisfies`].
+    pub fn satisfies(&self, dep: &str) -> bool {
+        satisfies(&self.name, &self.version, &self.provides, dep)
+    }
+
+    /// The record in the shape `pacman -Qi` prints it.
+    pub fn to_info(&self) -> RepoInfo {
+        RepoInfo {
+            repository: None,
+            name: self.name.clone(),
+            version: self.version.clone(),
+            description: self.description.clone(),
+            url: self.url.clone(),
+            licenses: self.licenses.clone(),
+            groups: self.groups.clone(),
+            provides: self.provides.clone(),
+            depends: self.depends.clone(),
+            optional_deps: self.optdepends.clone(),
+            conflicts: self.conflicts.clone(),
+            replaces: self.replaces.clone(),
+            download_size: None,
+            installed_size: format_size(self.size),
+            packager: self.packager.clone(),
+            build_date: self.build_date,
+            install_date: self.install_date,
+            install_reason: Some(
+                match self.reason {
+                    InstallReason::Explicit => "Explicitly installed",
+                    InstallReason::Dependency => "Installed as a dependency for another package",
+                }
+                .to_string(),
+            ),
+        }
+    }
+}
+
+/// Whether a package satisfies `dep` (`name[<|<=|=|>=|>version]`), by its
+/// own name and version or by one of its provides. A provide without a
+/// version only satisfies unversioned dependencies.
+pub(crate) fn satisfies(name: &str, version: &str, provides: &[String], dep: &str) -> bool {
+    let (wanted_name, constraint) = split_dep(dep);
+    let matches = |version: Option<&str>| match (constraint, version) {
+        (None, _) => true,
+        (Some(_), None) => false,
+        (Some((op, wanted)), Some(version)) => {
+            let ord = vercmp(version, wanted);
+            match op {
+                "<" => ord.is_lt(),
+                "<=" => ord.is_le(),
+                ">" => ord.is_gt(),
+                ">=" => ord.is_ge(),
+                _ => ord.is_eq(),
+            }
+        }
+    };
+
+    (name == wanted_name && matches(Some(version)))
+        || provides.iter().any(|provide| {
+            let (provided, version) = match provide.split_once('=') {
+                Some((provided, version)) => (provided, Some(version)),
+                None => (provide.as_str(), None),
+            };
+            provided == wanted_name && matches(version)
+        })
+}
+
+/// `foo>=1.0` -> `("foo", Some((">=", "1.0")))`.
+fn split_dep(dep: &str) -> (&str, Option<(&str, &str)>) {
+    let Some(start) = dep.find(['<', '>', '=']) else {
+        return (dep, None);
+    };
+    let op_len = dep[start..].bytes().take_while(|b| b"<>=".contains(b)).count();
+    let (op, version) = dep[start..].split_at(op_len);
+    (&dep[..start], Some((op, version)))
+}
+
+/// Sizes the way pacman prints them, e.g. `1.50 MiB`.
+fn format_size(bytes: u64) -> String {
+    const UNITS: [&str; 5] = ["B", "KiB", "MiB", "GiB", "TiB"];
+    let mut size = bytes as f64;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+    format!("{:.2} {}", size, UNITS[unit])
+}
+
+// ======================
+// Local database
+// ======================
+/// Every installed package, read from `<dbpath>/local`.
+#[derive(Debug, Default)]
+pub struct LocalDb {
+    packages: BTreeMap<String, LocalPackage>,
+}
+
+impl LocalDb {
+    /// The local database under the context's [`Context::dbpath`].
+    pub fn load(ctx: &Context) -> Result<LocalDb> {
+        LocalDb::open(&ctx.dbpath()?)
+    }
+
+    /// Read the local database under `dbpath`. A database without a `local`
+    /// directory has nothing installed yet.
+    pub fn open(dbpath: &Path) -> Result<LocalDb> {
+        if !dbpath.is_dir() {
+            return Err(RaurError::Config(format!("no pacman database at '{}'", dbpath.display())));
+        }
+        let local = dbpath.join("local");
+        if !local.is_dir() {
+            return Ok(LocalDb::default());
+        }
+
+        let mut packages = BTreeMap::new();
+        for entry in std::fs::read_dir(&local)? {
+            let entry = entry?;
+            if !entry.file_type()?.is_dir() {
+                continue;
+            }
+            let desc: PathBuf = entry.path().join("desc");
+            let text = match std::fs::read_to_string(&desc) {
+                Ok(text) => text,
+                // A half removed entry; pacman skips those as well
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => continue,
+                Err(e) => return Err(e.into()),
+            };
+            let pkg = LocalPackage::from_desc(&text)
+                .ok_or_else(|| RaurError::Other(format!("{}: no %NAME% or %VERSION%", desc.display())))?;
+            packages.insert(pkg.name.clone(), pkg);
+        }
+
+        Ok(LocalDb { packages })
+    }
+
+    pub fn package(&self, name: &str) -> Option<&LocalPackage> {
+        self.packages.get(name)
+    }
+
+    /// All installed packages, by name.
+    pub fn packages(&self) -> impl Iterator<Item = &LocalPackage> {
+        self.packages.values()
+    }
+
+    pub fn is_installed(&self, name: &str) -> bool {
+        self.packages.contains_key(name)
+    }
+
+    /// The installed package that satisfies `dep`, if any; the package of
+    /// that name is preferred over providers.
+    pub fn satisfier(&self, dep: &str) -> Option<&LocalPackage> {
+        let (name, _) = split_dep(dep);
+        self.package(name)
+            .filter(|pkg| pkg.satisfies(dep))
+            .or_else(|| self.packages().find(|pkg| pkg.satisfies(dep)))
+    }
+}
+
+// ======================
+// Sync databases
+// ======================
+/// A package in a sync database.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct SyncPackage {
+    /// The database it was read from, e.g. `extra`
+    pub repo: String,
+    pub name: String,
+    pub version: String,
+    pub base: Option<String>,
+    pub description: String,
+    pub url: String,
+    pub arch: String,
+    pub build_date: Option<i64>,
+    pub packager: String,
+    /// File name in the repo's mirrors
+    pub filename: String,
+    /// Download and installed size in bytes
+    pub download_size: u64,
+    pub size: u64,
+    pub licenses: Vec<String>,
+    pub groups: Vec<String>,
+    pub depends: Vec<String>,
+    /// `name: reason` entries
+    pub optdepends: Vec<String>,
+    pub makedepends: Vec<String>,
+    pub checkdepends: Vec<String>,
+    pub conflicts: Vec<String>,
+    pub provides: Vec<String>,
+    pub replaces: Vec<String>,
+}
+
+impl SyncPackage {
+    fn from_desc(repo: &str, text: &str) -> Option<SyncPackage> {
+        let mut fields = parse_desc(text);
+        let mut list = |key: &str| fields.remove(key).unwrap_or_default();
+        let first = |values: Vec<String>| values.into_iter().next();
+
+        Some(SyncPackage {
+            repo: repo.to_string(),
+            name: first(list("NAME"))?,
+            version: first(list("VERSION"))?,
+            base: first(list("BASE")),
+            description: first(list("DESC")).unwrap_or_default(),
+            url: first(list("URL")).unwrap_or_default(),
+            arch: first(list("ARCH")).unwrap_or_default(),
+            build_date: first(list("BUILDDATE")).and_then(|date| date.parse().ok()),
+            packager: first(list("PACKAGER")).unwrap_or_default(),
+            filename: first(list("FILENAME")).unwrap_or_default(),
+            download_size: first(list("CSIZE")).and_then(|size| size.parse().ok()).unwrap_or(0),
+            size: first(list("ISIZE")).and_then(|size| size.parse().ok()).unwrap_or(0),
+            licenses: list("LICENSE"),
+            groups: list("GROUPS"),
+            depends: list("DEPENDS"),
+            optdepends: list("OPTDEPENDS"),
+            makedepends: list("MAKEDEPENDS"),
+            checkdepends: list("CHECKDEPENDS"),
+            conflicts: list("CONFLICTS"),
+            provides: list("PROVIDES"),
+            replaces: list("REPLACES"),
+        })
+    }
+
+    /// Whether this package satisfies `dep`, see [`satisfies`].
+    pub fn satisfies(&self, dep: &str) -> bool {
+        satisfies(&self.name, &self.version, &self.provides, dep)
+    }
+
+    /// Whether the package provides `name`, with or without a version.
+    pub fn provides(&self, name: &str) -> bool {
+        self.provides.iter().any(|provide| dep_name(provide) == name)
+    }
+
+    /// The record in the shape `pacman -Si` prints it.
+    pub fn to_info(&self) -> RepoInfo {
+        RepoInfo {
+            repository: Some(self.repo.clone()),
+            name: self.name.clone(),
+            version: self.version.clone(),
+            description: self.description.clone(),
+            url: self.url.clone(),
+            licenses: self.licenses.clone(),
+            groups: self.groups.clone(),
+            provides: self.provides.clone(),
+            depends: self.depends.clone(),
+            optional_deps: self.optdepends.clone(),
+            conflicts: self.conflicts.clone(),
+            replaces: self.replaces.clone(),
+            download_size: Some(format_size(self.download_size)),
+            installed_size: format_size(self.size),
+            packager: self.packager.clone(),
+            build_date: self.build_date,
+            install_date: None,
+            install_reason: None,
+        }
+    }
+}
+
+/// One repo's sync database.
+#[derive(Debug)]
+pub struct SyncDb {
+    pub name: String,
+    packages: BTreeMap<String, SyncPackage>,
+}
+
+impl SyncDb {
+    /// Read the database archive at `path` as the repo `name`.
+    pub fn open(name: &str, path: &Path) -> Result<SyncDb> {
+        let invalid = |e: std::io::Error| RaurError::Other(format!("{}: {}", path.display(), e));
+        let data = std::fs::read(path)?;
+
+        // pacman picks the decompressor by content, not by file name
+        let reader: Box<dyn Read + '_> = match data.as_slice() {
+            [0x1f, 0x8b, ..] => Box::new(flate2::read::GzDecoder::new(data.as_slice())),
+            [0x28, 0xb5, 0x2f, 0xfd, ..] => Box::new(zstd::Decoder::new(data.as_slice()).map_err(invalid)?),
+            _ if data.get(257..262) == Some(b"ustar") => Box::new(data.as_slice()),
+            // An empty repo is an empty file
+            [] => return Ok(SyncDb { name: name.to_string(), packages: BTreeMap::new() }),
+            _ => return Err(RaurError::Other(format!("{}: unsupported compression", path.display()))),
+        };
+
+        // Entries are `<name>-<version>/desc`; old databases split off a
+        // `depends` file, which is read along with it
+        let mut entries: BTreeMap<String, String> = BTreeMap::new();
+        let mut archive = tar::Archive::new(reader);
+        for entry in archive.entries().map_err(invalid)? {
+            let mut entry = entry.map_err(invalid)?;
+            let entry_path = entry.path().map_err(invalid)?.to_path_buf();
+            let (Some(dir), Some(file)) = (entry_path.parent(), entry_path.file_name()) else {
+                continue;
+            };
+            if file != "desc" && file != "depends" {
+                continue;
+            }
+            let dir = dir.to_string_lossy().to_string();
+            let mut text = String::new();
+            entry.read_to_string(&mut text).map_err(invalid)?;
+            let desc = entries.entry(dir).or_default();
+            desc.push_str(&text);
+            desc.push('\n');
+        }
+
+        let mut packages = BTreeMap::new();
+        for (dir, text) in entries {
+            let pkg = SyncPackage::from_desc(name, &text)
+                .ok_or_else(|| RaurError::Other(format!("{}: {} has no %NAME% or %VERSION%", path.display(), dir)))?;
+            packages.insert(pkg.name.clone(), pkg);
+        }
+
+        Ok(SyncDb {
+            name: name.to_string(),
+            packages,
+        })
+    }
+
+    pub fn package(&self, name: &str) -> Option<&SyncPackage> {
+        self.packages.get(name)
+    }
+
+    /// All packages, by name.
+    pub fn packages(&self) -> impl Iterator<Item = &SyncPackage> {
+        self.packages.values()
+    }
+}
+
+/// All sync databases, in the order pacman uses them: the first repo with a
+/// package wins.
+#[derive(Debug, Default)]
+pub struct SyncDbs {
+    dbs: Vec<SyncDb>,
+}
+
+impl SyncDbs {
+    /// Every `<dbpath>/sync/*.db`, in the order of their names.
+    pub fn open(dbpath: &Path) -> Result<SyncDbs> {
+        let sync = dbpath.join("sync");
+        if !sync.is_dir() {
+            return Err(RaurError::Config(format!(
+                "no sync databases in '{}', run 'raur update' first",
+                sync.display()
+            )));
+        }
+
+        let mut repos = Vec::new();
+        for entry in std::fs::read_dir(&sync)? {
+            let path = entry?.path();
+            if path.extension().is_some_and(|ext| ext == "db") {
+                if let Some(name) = path.file_stem() {
+                    repos.push(name.to_string_lossy().to_string());
+                }
+            }
+        }
+        repos.sort();
+
+        let repos: Vec<&str> = repos.iter().map(String::as_str).collect();
+        SyncDbs::open_repos(dbpath, &repos)
+    }
+
+    /// The databases of the given repos, in that order.
+    pub fn open_repos(dbpath: &Path, repos: &[&str]) -> Result<SyncDbs> {
+        let mut dbs = Vec::new();
+        for repo in repos {
+            let path = dbpath.join("sync").join(format!("{}.db", repo));
+            if !path.exists() {
+                return Err(RaurError::Config(format!(
+                    "database file for '{}' does not exist, run 'raur update' first",
+                    repo
+                )));
+            }
+            dbs.push(SyncDb::open(repo, &path)?);
+        }
+        Ok(SyncDbs { dbs })
+    }
+
+    pub fn dbs(&self) -> &[SyncDb] {
+        &self.dbs
+    }
+
+    /// All packages, repo by repo.
+    pub fn packages(&self) -> impl Iterator<Item = &SyncPackage> {
+        self.dbs.iter().flat_map(SyncDb::packages)
+    }
+
+    /// The package called `name` in the first repo that has it.
+    pub fn package(&self, name: &str) -> Option<&SyncPackage> {
+        self.dbs.iter().find_map(|db| db.package(name))
+    }
+
+    /// Packages other than `name` itself that provide it.
+    pub fn providers(&self, name: &str) -> Vec<&SyncPackage> {
+        self.packages().filter(|pkg| pkg.name != name && pkg.provides(name)).collect()
+    }
+
+    /// The members of a package group.
+    pub fn group(&self, name: &str) -> Vec<&SyncPackage> {
+        self.packages().filter(|pkg| pkg.groups.iter().any(|group| group == name)).collect()
+    }
+
+    /// The package pacman would install for `dep`: the one of that name if
+    /// its version fits, otherwise the first provider that does.
+    pub fn satisfier(&self, dep: &str) -> Option<&SyncPackage> {
+        self.package(dep_name(dep))
+            .filter(|pkg| pkg.satisfies(dep))
+            .or_else(|| self.packages().find(|pkg| pkg.satisfies(dep)))
+    }
+
+    /// Packages whose name (or, with `description`, also description and
+    /// provides) matches the case insensitive regex `pattern`, like
+    /// `pacman -Ss`.
+    pub fn search(&self, pattern: &str, description: bool) -> Result<Vec<&SyncPackage>> {
+        let regex = RegexBuilder::new(pattern)
+            .case_insensitive(true)
+            .build()
+            .map_err(|e| RaurError::Other(format!("invalid search pattern '{}': {}", pattern, e)))?;
+
+        Ok(self
+            .packages()
+            .filter(|pkg| {
+                regex.is_match(&pkg.name)
+                    || (description
+                        && (regex.is_match(&pkg.description)
+                            || pkg.provides.iter().any(|provide| regex.is_match(dep_name(provide)))))
+            })
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn package(name: &str, version: &str, provides: &[&str]) -> LocalPackage {
+        let provides: String = provides.iter().map(|p| format!("{}\n", p)).collect();
+        LocalPackage::from_desc(&format!("%NAME%\n{}\n\n%VERSION%\n{}\n\n%PROVIDES%\n{}\n", name, version, provides)).unwrap()
+    }
+
+    #[test]
+    fn dependencies_match_names_and_provides() {
+        let bash = package("bash", "5.2.037-1", &["sh"]);
+        assert!(bash.satisfies("bash"));
+        assert!(bash.satisfies("bash>=5"));
+        assert!(!bash.satisfies("bash<5"));
+        assert!(bash.satisfies("sh"));
+        // Unversioned provides do not satisfy versioned dependencies
+        assert!(!bash.satisfies("sh>=1"));
+
+        let java = package("jre-openjdk", "23.0.1-1", &["java-runtime=23", "jre"]);
+        assert!(java.satisfies("java-runtime>=17"));
+        assert!(!java.satisfies("java-runtime=17"));
+        assert!(!java.satisfies("java"));
+    }
+
+    #[test]
+    fn sizes_and_dates_read_like_pacman() {
+        assert_eq!(format_size(512), "512.00 B");
+        assert_eq!(format_size(1572864), "1.50 MiB");
+
+        // Dates stay timestamps, `raur info` formats them like the AUR's
+        let desc = "%NAME%\nfoo\n\n%VERSION%\n1-1\n\n%BUILDDATE%\n1709251199\n\n%INSTALLDATE%\n0\n";
+        let info = LocalPackage::from_desc(desc).unwrap().to_info();
+        assert_eq!(info.build_date, Some(1709251199));
+        assert_eq!(info.install_date, Some(0));
+    }
+}
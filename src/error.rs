@@ -0,0 +1,151 @@
+//! Errors returned by the library and the exit codes the CLI maps them to.
+//!
+//! | code | error                               |
+//! |------|-------------------------------------|
+//! | 1    | anything else (I/O, invalid input)  |
+//! | 3    | network error talking to the AUR    |
+//! | 4    | the AUR RPC answered with an error  |
+//! | 5    | package or dependency not found     |
+//! | 6    | git clone/fetch/fast-forward failed |
+//! | 7    | makepkg failed                      |
+//! | 8    | a pacman transaction failed         |
+//! | 9    | missing permissions                 |
+//! | 10   | aborted by the user                 |
+//! | 11   | invalid configuration               |
+//! | 12   | a question needs a terminal         |
+//! | 13   | AUR dependencies form a cycle       |
+//!
+//! Exit code 2 is left to clap for usage errors.
+
+use std::fmt;
+use std::path::PathBuf;
+
+use serde::Serialize;
+
+use crate::runner::{Cmd, CmdOutput};
+use crate::srcinfo::SrcinfoError;
+
+pub type Result<T, E = RaurError> = std::result::Result<T, E>;
+
+#[derive(Debug, thiserror::Error)]
+pub enum RaurError {
+    #[error("network error: {0}")]
+    Network(#[from] reqwest::Error),
+    #[error("AUR RPC error: {0}")]
+    Rpc(String),
+    #[error("could not find {}", describe_missing(.0))]
+    NotFound(Vec<Missing>),
+    #[error("could not update the clone of '{pkgbase}': {failure}")]
+    Clone { pkgbase: String, failure: CommandFailure },
+    #[error(
+        "the clone of '{pkgbase}' has diverged from the AUR, merge or drop the local changes in {}: {failure}",
+        .clone_dir.display()
+    )]
+    CloneDiverged { pkgbase: String, clone_dir: PathBuf, failure: CommandFailure },
+    #[error("failed to build '{pkgbase}': {failure}")]
+    Build { pkgbase: String, failure: CommandFailure },
+    #[error("{0}")]
+    Pacman(CommandFailure),
+    #[error("permission denied: {0}")]
+    Permission(String),
+    #[error("{0}")]
+    UserAbort(String),
+    #[error("{0}")]
+    Config(String),
+    #[error("cannot ask {0}: stdin is not a terminal (pass --noconfirm or set an answer policy)")]
+    NotInteractive(String),
+    #[error("dependency cycle detected: {}", .0.join(" -> "))]
+    DependencyCycle(Vec<String>),
+    #[error("{}: {error}", .path.display())]
+    Srcinfo { path: PathBuf, error: SrcinfoError },
+    #[error("{}: line {line}: {message}", .path.display())]
+    PacmanConf { path: PathBuf, line: usize, message: String },
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+    #[error("{0}")]
+    Other(String),
+}
+
+impl RaurError {
+    /// Process exit code for the error, see the module docs.
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            RaurError::Io(_) | RaurError::Srcinfo { .. } | RaurError::Other(_) => 1,
+            RaurError::Network(_) => 3,
+            RaurError::Rpc(_) => 4,
+            RaurError::NotFound(_) => 5,
+            RaurError::Clone { .. } | RaurError::CloneDiverged { .. } => 6,
+            RaurError::Build { .. } => 7,
+            RaurError::Pacman(_) => 8,
+            RaurError::Permission(_) => 9,
+            RaurError::UserAbort(_) => 10,
+            RaurError::Config(_) | RaurError::PacmanConf { .. } => 11,
+            RaurError::NotInteractive(_) => 12,
+            RaurError::DependencyCycle(_) => 13,
+        }
+    }
+
+    /// The failed command behind the error, if any.
+    pub fn command_failure(&self) -> Option<&CommandFailure> {
+        match self {
+            RaurError::Clone { failure, .. }
+            | RaurError::CloneDiverged { failure, .. }
+            | RaurError::Build { failure, .. }
+            | RaurError::Pacman(failure) => Some(failure),
+            _ => None,
+        }
+    }
+}
+
+/// A package, or a versioned dependency like `foo>=2`, that could not be
+/// found, with what asked for it.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Missing {
+    pub name: String,
+    /// The package depending on it, `None` for a requested target
+    pub required_by: Option<String>,
+}
+
+fn describe_missing(missing: &[Missing]) -> String {
+    missing
+        .iter()
+        .map(|m| match &m.required_by {
+            Some(parent) => format!("'{}' (required by '{}')", m.name, parent),
+            None => format!("'{}'", m.name),
+        })
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// An external command that exited unsuccessfully.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct CommandFailure {
+    pub command: String,
+    /// `None` when the process was killed by a signal
+    pub code: Option<i32>,
+    pub stderr: String,
+}
+
+impl CommandFailure {
+    pub fn new(cmd: &Cmd, output: &CmdOutput) -> Self {
+        CommandFailure {
+            command: cmd.to_string(),
+            code: output.code,
+            stderr: output.stderr.clone(),
+        }
+    }
+}
+
+impl fmt::Display for CommandFailure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.code {
+            Some(code) => write!(f, "'{}' exited with code {}", self.command, code)?,
+            None => write!(f, "'{}' was killed by a signal", self.command)?,
+        }
+        // The last line usually holds the actual error message
+        if let Some(line) = self.stderr.lines().rev().find(|line| !line.trim().is_empty()) {
+            write!(f, " ({})", line.trim())?;
+        }
+        Ok(())
+    }
+}
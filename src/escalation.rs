@@ -0,0 +1,183 @@
+//! Running pacman as root through sudo, doas, run0 or pkexec.
+//!
+//! sudo and doas cache credentials for a few minutes, which a long AUR
+//! build easily outlasts. While packages build, a [`Keepalive`] refreshes
+//! the cached credentials so the final `pacman -U` does not stop at a
+//! password prompt. run0 and pkexec ask polkit every time and have nothing
+//! to refresh.
+
+use std::fmt;
+use std::str::FromStr;
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+use serde::Serialize;
+
+use crate::error::{CommandFailure, RaurError, Result};
+use crate::runner::{Cmd, CmdOutput};
+use crate::Context;
+
+/// How often a [`Keepalive`] refreshes credentials. sudo forgets them after
+/// 5 minutes by default.
+pub const KEEPALIVE_INTERVAL: Duration = Duration::from_secs(60);
+
+/// The program used to run commands as root.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Escalation {
+    #[default]
+    Sudo,
+    Doas,
+    Run0,
+    Pkexec,
+}
+
+impl Escalation {
+    pub const ALL: [Escalation; 4] = [Escalation::Sudo, Escalation::Doas, Escalation::Run0, Escalation::Pkexec];
+
+    pub fn program(self) -> &'static str {
+        match self {
+            Escalation::Sudo => "sudo",
+            Escalation::Doas => "doas",
+            Escalation::Run0 => "run0",
+            Escalation::Pkexec => "pkexec",
+        }
+    }
+
+    /// `program` run as root.
+    pub fn command(self, program: &str) -> Cmd {
+        Cmd::new(self.program()).arg(program)
+    }
+
+    /// Authenticates up front, asking for a password if needed.
+    fn validate(self) -> Option<Cmd> {
+        match self {
+            Escalation::Sudo => Some(Cmd::new("sudo").arg("-v")),
+            Escalation::Doas => Some(Cmd::new("doas").arg("true")),
+            Escalation::Run0 | Escalation::Pkexec => None,
+        }
+    }
+
+    /// Refreshes cached credentials without ever asking.
+    fn refresh(self) -> Option<Cmd> {
+        match self {
+            Escalation::Sudo => Some(Cmd::new("sudo").args(["-n", "-v"])),
+            Escalation::Doas => Some(Cmd::new("doas").args(["-n", "true"])),
+            Escalation::Run0 | Escalation::Pkexec => None,
+        }
+    }
+
+    /// Whether a failed command was refused by the escalation program
+    /// (wrong password, not allowed) rather than failing itself.
+    pub fn refused(self, output: &CmdOutput) -> bool {
+        match self {
+            Escalation::Sudo | Escalation::Doas => {
+                let prefix = format!("{}:", self.program());
+                output.stderr.lines().any(|line| line.starts_with(&prefix))
+            }
+            Escalation::Run0 => {
+                output.stderr.contains("Interactive authentication required") || output.stderr.contains("Access denied")
+            }
+            // 126: the dialog was dismissed, 127: not authorized
+            Escalation::Pkexec => matches!(output.code, Some(126) | Some(127)),
+        }
+    }
+}
+
+impl fmt::Display for Escalation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.program())
+    }
+}
+
+impl FromStr for Escalation {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Escalation::ALL
+            .into_iter()
+            .find(|backend| backend.program() == s)
+            .ok_or_else(|| "expected one of sudo, doas, run0, pkexec".to_string())
+    }
+}
+
+/// Keeps the credentials of the configured backend fresh until dropped.
+pub struct Keepalive {
+    stop: Option<mpsc::Sender<()>>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl Keepalive {
+    /// Authenticate now and refresh every [`KEEPALIVE_INTERVAL`]. Does
+    /// nothing for backends without cached credentials.
+    pub fn start(ctx: &Context) -> Result<Keepalive> {
+        Keepalive::with_interval(ctx, KEEPALIVE_INTERVAL)
+    }
+
+    pub fn with_interval(ctx: &Context, interval: Duration) -> Result<Keepalive> {
+        let escalation = ctx.config().escalation;
+        let (Some(validate), Some(refresh)) = (escalation.validate(), escalation.refresh()) else {
+            return Ok(Keepalive { stop: None, thread: None });
+        };
+
+        let status = ctx.runner().status(&validate)?;
+        if !status.success() {
+            return Err(RaurError::Permission(CommandFailure::new(&validate, &status).to_string()));
+        }
+
+        let runner = ctx.shared_runner();
+        let (stop, stopped) = mpsc::channel::<()>();
+        let thread = thread::spawn(move || {
+            // Runs until the sender is dropped
+            while let Err(RecvTimeoutError::Timeout) = stopped.recv_timeout(interval) {
+                // Not fatal: at worst pacman asks for the password again
+                let _ = runner.output(&refresh);
+            }
+        });
+
+        Ok(Keepalive {
+            stop: Some(stop),
+            thread: Some(thread),
+        })
+    }
+}
+
+impl Drop for Keepalive {
+    fn drop(&mut self) {
+        self.stop.take();
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn refusals_are_told_apart_from_failures() {
+        let stderr = |text: &str| CmdOutput {
+            code: Some(1),
+            stdout: String::new(),
+            stderr: text.into(),
+        };
+
+        assert!(Escalation::Sudo.refused(&stderr("sudo: a password is required\n")));
+        assert!(!Escalation::Sudo.refused(&stderr("error: target not found: foo\n")));
+        assert!(Escalation::Doas.refused(&stderr("doas: Authentication failed\n")));
+        assert!(!Escalation::Doas.refused(&stderr("sudo: a password is required\n")));
+        assert!(Escalation::Run0.refused(&stderr("Failed to start transient service unit: Access denied\n")));
+        assert!(Escalation::Pkexec.refused(&CmdOutput::failed(126)));
+        assert!(!Escalation::Pkexec.refused(&CmdOutput::failed(1)));
+    }
+
+    #[test]
+    fn backends_parse_from_their_program_name() {
+        for backend in Escalation::ALL {
+            assert_eq!(backend.to_string().parse::<Escalation>(), Ok(backend));
+        }
+        assert!("su".parse::<Escalation>().is_err());
+    }
+}
@@ -0,0 +1,200 @@
+//! raur — a minimal AUR helper.
+//!
+//! The library holds everything the `raur` binary does: querying the AUR
+//! ([`aur`]) and pacman ([`pacman`], [`db`]), resolving targets and
+//! dependencies ([`resolve`]), installing, cloning and building packages
+//! ([`build`]) and talking to the user ([`ui`]), all configured by a layered
+//! [`config::Config`]. External programs are run through the
+//! [`CommandRunner`] of a [`Context`], so all of it can be driven by a
+//! scripted runner in tests.
+
+pub mod aur;
+pub mod build;
+pub mod config;
+pub mod db;
+pub mod error;
+pub mod escalation;
+pub mod pacman;
+pub mod pacman_conf;
+pub mod resolve;
+pub mod runner;
+pub mod srcinfo;
+pub mod ui;
+pub mod version;
+
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+
+use aur::AurClient;
+use config::Config;
+use db::{SyncDbs, DB_PATH};
+pub use error::{RaurError, Result};
+use pacman_conf::{PacmanConf, PACMAN_CONF};
+use runner::{CommandRunner, SystemRunner};
+use ui::{Prompter, TerminalPrompter};
+
+/// Shared state of a raur invocation.
+pub struct Context {
+    runner: Arc<dyn CommandRunner>,
+    aur: AurClient,
+    config: Config,
+    prompter: Box<dyn Prompter>,
+    /// Read on first use, see [`Context::pacman_conf`]
+    pacman_conf: Mutex<Option<Arc<PacmanConf>>>,
+    /// Read on first use, see [`Context::sync_dbs`]
+    sync_dbs: Mutex<Option<Arc<SyncDbs>>>,
+}
+
+impl Default for Context {
+    fn default() -> Self {
+        Context::new()
+    }
+}
+
+impl Context {
+    /// A context with the default settings that runs real commands and asks
+    /// on the terminal.
+    pub fn new() -> Self {
+        Context::from_config(Config::default())
+    }
+
+    /// Like [`Context::new`], with the AUR client built from `config`.
+    pub fn from_config(config: Config) -> Self {
+        Context {
+            runner: Arc::new(SystemRunner::default()),
+            aur: AurClient::with_base_url(&config.aur_url),
+            config,
+            prompter: Box::new(TerminalPrompter),
+            pacman_conf: Mutex::new(None),
+            sync_dbs: Mutex::new(None),
+        }
+    }
+
+    pub fn with_runner(mut self, runner: impl CommandRunner + 'static) -> Self {
+        self.runner = Arc::new(runner);
+        self
+    }
+
+    pub fn with_aur(mut self, aur: AurClient) -> Self {
+        self.aur = aur;
+        self
+    }
+
+    pub fn with_cache_dir(mut self, cache_dir: impl Into<PathBuf>) -> Self {
+        self.config.cache_dir = Some(cache_dir.into());
+        self
+    }
+
+    pub fn with_pacman_conf(mut self, pacman_conf: impl Into<PathBuf>) -> Self {
+        self.config.pacman_conf = pacman_conf.into();
+        self
+    }
+
+    pub fn with_dbpath(mut self, dbpath: impl Into<PathBuf>) -> Self {
+        self.config.dbpath = Some(dbpath.into());
+        self
+    }
+
+    pub fn with_prompter(mut self, prompter: impl Prompter + 'static) -> Self {
+        self.prompter = Box::new(prompter);
+        self
+    }
+
+    pub fn runner(&self) -> &dyn CommandRunner {
+        self.runner.as_ref()
+    }
+
+    /// The runner, for work that outlives a borrow of the context.
+    pub(crate) fn shared_runner(&self) -> Arc<dyn CommandRunner> {
+        self.runner.clone()
+    }
+
+    pub fn aur(&self) -> &AurClient {
+        &self.aur
+    }
+
+    pub fn config(&self) -> &Config {
+        &self.config
+    }
+
+    /// pacman's configuration, read on first use. A missing
+    /// `/etc/pacman.conf` reads as pacman's defaults, any other missing file
+    /// is an error.
+    pub fn pacman_conf(&self) -> Result<Arc<PacmanConf>> {
+        let mut cached = self.pacman_conf.lock().unwrap();
+        if let Some(conf) = cached.as_ref() {
+            return Ok(conf.clone());
+        }
+        let path = &self.config.pacman_conf;
+        let conf = if path.as_path() == std::path::Path::new(PACMAN_CONF) && !path.exists() {
+            PacmanConf::default()
+        } else {
+            PacmanConf::load(path)?
+        };
+        let conf = Arc::new(conf);
+        *cached = Some(conf.clone());
+        Ok(conf)
+    }
+
+    /// pacman's database directory: the `dbpath` setting, else the
+    /// `DBPath` of pacman.conf, else pacman's default.
+    pub fn dbpath(&self) -> Result<PathBuf> {
+        if let Some(dbpath) = &self.config.dbpath {
+            return Ok(dbpath.clone());
+        }
+        Ok(self.pacman_conf()?.db_path.clone().unwrap_or_else(|| PathBuf::from(DB_PATH)))
+    }
+
+    /// The sync databases of the repos in pacman.conf, or all of them when
+    /// it lists none. They are read once and kept until pacman refreshes
+    /// them.
+    pub fn sync_dbs(&self) -> Result<Arc<SyncDbs>> {
+        let mut cached = self.sync_dbs.lock().unwrap();
+        if let Some(dbs) = cached.as_ref() {
+            return Ok(dbs.clone());
+        }
+        let conf = self.pacman_conf()?;
+        let dbpath = self.dbpath()?;
+        let dbs = if conf.repos.is_empty() {
+            SyncDbs::open(&dbpath)?
+        } else {
+            SyncDbs::open_repos(&dbpath, &conf.repo_names())?
+        };
+        let dbs = Arc::new(dbs);
+        *cached = Some(dbs.clone());
+        Ok(dbs)
+    }
+
+    /// Drop the cached sync databases after pacman downloaded new ones.
+    pub(crate) fn forget_sync_dbs(&self) {
+        self.sync_dbs.lock().unwrap().take();
+    }
+
+    /// Directory holding the AUR clones, created on first use.
+    pub fn cache_dir(&self) -> Result<PathBuf> {
+        let cache_dir = self.config.cache_dir()?;
+        if !cache_dir.exists() {
+            std::fs::create_dir_all(&cache_dir).map_err(|e| match e.kind() {
+                std::io::ErrorKind::PermissionDenied => {
+                    RaurError::Permission(format!("cannot create {}: {}", cache_dir.display(), e))
+                }
+                _ => e.into(),
+            })?;
+        }
+        Ok(cache_dir)
+    }
+
+    /// Ask the user a question and return the answer line.
+    pub fn prompt(&self, question: &str) -> Result<String> {
+        self.ensure_interactive(&format!("'{}'", question.trim().trim_end_matches(':')))?;
+        Ok(self.prompter.prompt(question)?)
+    }
+
+    /// Fail instead of waiting for an answer that cannot come.
+    pub fn ensure_interactive(&self, what: &str) -> Result<()> {
+        if !self.prompter.is_interactive() {
+            return Err(RaurError::NotInteractive(what.to_string()));
+        }
+        Ok(())
+    }
+}
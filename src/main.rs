@@ -1,20 +1,91 @@
 use clap::{Parser, Subcommand};
-use serde::Deserialize;
 use colored::*;
-use prettytable::{Table, Row, Cell};
-use std::process::Command;
-use std::path::Path;
-use indicatif::{ProgressBar, ProgressStyle};
-use std::io::{stdin, stdout, Write};
-use std::time::Duration;
-use std::env;
+use raur::aur::SearchBy;
+use raur::build::{self, AurInstall, CleanPolicy};
+use raur::config::{self, Config, Source};
+use raur::pacman;
+use raur::runner::SystemRunner;
+use raur::ui::{self, Document, Format};
+use raur::{resolve, Context, RaurError, Result};
+use serde::Serialize;
+use std::fmt::Display;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
 
 #[derive(Parser, Debug)]
 #[command(name = "raur")]
 #[command(version, about = "AUR + Pacman helper written in Rust", long_about = None)]
+#[command(arg_required_else_help = true)]
 struct Cli {
     #[command(subcommand)]
-    command: Commands,
+    command: Option<Commands>,
+    /// Output format of results
+    #[arg(long, global = true, value_enum, default_value_t = Format::Human)]
+    format: Format,
+    /// Shorthand for --format json
+    #[arg(long, global = true)]
+    json: bool,
+    /// Directory for AUR clones (overrides cache_dir)
+    #[arg(long, global = true, value_name = "DIR")]
+    cache_dir: Option<String>,
+    /// AUR base URL (overrides aur_url)
+    #[arg(long, global = true, value_name = "URL")]
+    aur_url: Option<String>,
+    /// Alternate pacman.conf (overrides pacman_conf)
+    #[arg(long, global = true, value_name = "FILE")]
+    config: Option<String>,
+    /// pacman database directory (overrides dbpath)
+    #[arg(short = 'b', long, global = true, value_name = "DIR")]
+    dbpath: Option<String>,
+    /// AUR clones fetched at the same time (overrides jobs)
+    #[arg(short = 'j', long, global = true, value_name = "N")]
+    jobs: Option<String>,
+    /// Never ask: pass --noconfirm to pacman and answer open questions
+    /// (review: skip, provider: first, remove: yes)
+    #[arg(long, visible_alias = "yes", global = true, conflicts_with = "confirm")]
+    noconfirm: bool,
+    /// Ask every question, including pacman's and makepkg's
+    #[arg(long, global = true)]
+    confirm: bool,
+    /// Search repos and AUR, then pick packages to install from a numbered list
+    query: Vec<String>,
+}
+
+impl Cli {
+    fn format(&self) -> Format {
+        if self.json { Format::Json } else { self.format }
+    }
+
+    /// Defaults, config files and environment, then the flags on top.
+    fn config(&self) -> Result<Config> {
+        // `config set` has to work to repair a config that does not load
+        if let Some(Commands::Config { action: ConfigAction::Set { .. } }) = &self.command {
+            return Ok(Config::default());
+        }
+
+        let mut config = Config::load()?;
+        let flags = [
+            ("--cache-dir", "cache_dir", &self.cache_dir),
+            ("--aur-url", "aur_url", &self.aur_url),
+            ("--config", "pacman_conf", &self.config),
+            ("--dbpath", "dbpath", &self.dbpath),
+            ("--jobs", "jobs", &self.jobs),
+        ];
+        for (flag, key, value) in flags {
+            if let Some(value) = value {
+                config
+                    .set(key, value, Source::Cli)
+                    .map_err(|e| RaurError::Config(format!("{}: {}", flag, e)))?;
+            }
+        }
+        if self.noconfirm {
+            config.unattended();
+        }
+        if self.confirm {
+            config.confirm_all();
+        }
+        Ok(config)
+    }
 }
 
 #[derive(Subcommand, Debug)]
@@ -26,13 +97,32 @@ enum Commands {
         pacman_only: bool,
         #[arg(long, help = "Search only AUR")]
         aur_only: bool,
+        #[arg(long, value_enum, default_value_t = SearchBy::NameDesc, help = "Package field to search")]
+        by: SearchBy,
+        #[arg(long, help = "Results shown per source, 0 for all (overrides search_limit)")]
+        limit: Option<usize>,
+    },
+    /// Show package details from the repos or the AUR
+    Info {
+        #[arg(required = true)]
+        packages: Vec<String>,
     },
     /// Install a package
     Install {
+        /// Package names; repo/name or aur/name picks where one comes from
         packages: Vec<String>,
         #[arg(short = 'c', long = "cascade")]
         cascade: bool,
     },
+    /// Build AUR packages and their AUR dependencies into a directory
+    /// without installing them
+    Build {
+        #[arg(required = true)]
+        packages: Vec<String>,
+        /// Directory for the package files and their manifest.json
+        #[arg(short, long, value_name = "DIR")]
+        output: PathBuf,
+    },
     /// Remove a package
     Remove {
         packages: Vec<String>,
@@ -49,44 +139,122 @@ enum Commands {
         #[arg(short = 'y', long)]
         full: bool,
     },
+    /// Clean the AUR clone cache
+    Clean {
+        #[arg(long, value_enum, default_value_t = CleanPolicy::KeepInstalled)]
+        policy: CleanPolicy,
+    },
+    /// Show or change settings
+    Config {
+        #[command(subcommand)]
+        action: ConfigAction,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+enum ConfigAction {
+    /// Print the value of a setting
+    Get { key: String },
+    /// Write a setting to the user config
+    Set {
+        key: String,
+        value: String,
+        #[arg(long, help = "Write to /etc/raur.conf instead")]
+        system: bool,
+    },
+    /// Print all settings and where they come from
+    List,
 }
 
-#[derive(Debug, Deserialize)]
-struct AurResponse {
-    resultcount: i32,
-    results: Vec<AurPackage>,
+/// Prints results in the selected `--format`. Progress messages only go
+/// to humans; JSON and plain output carry nothing but the result.
+#[derive(Clone, Copy)]
+struct Output {
+    format: Format,
 }
 
-#[derive(Debug, Deserialize)]
-struct AurPackage {
-    #[serde(rename = "Name")]
-    name: String,
-    #[serde(rename = "Version")]
-    version: String,
-    #[serde(rename = "Description")]
-    description: Option<String>,
+impl Output {
+    fn is_human(&self) -> bool {
+        self.format == Format::Human
+    }
+
+    fn human(&self, msg: impl Display) {
+        if self.is_human() {
+            write_line(msg);
+        }
+    }
+
+    fn plain(&self, line: impl Display) {
+        if self.format == Format::Plain {
+            write_line(line);
+        }
+    }
+
+    fn json<T: Serialize>(&self, kind: &str, data: &T) {
+        if self.format == Format::Json {
+            write_line(Document::new(kind, data).to_json());
+        }
+    }
+}
+
+/// `println!` without the panic when stdout is gone: a reader that stopped
+/// early (`raur search foo --format plain | head`) ends raur quietly.
+fn write_line(line: impl Display) {
+    if let Err(e) = writeln!(io::stdout().lock(), "{}", line) {
+        if e.kind() != io::ErrorKind::BrokenPipe {
+            eprintln!("error: cannot write to stdout: {}", e);
+            std::process::exit(1);
+        }
+        std::process::exit(0);
+    }
 }
 
 #[tokio::main]
-async fn main() -> Result<(), Box<dyn std::error::Error>> {
+async fn main() {
     let cli = Cli::parse();
+    let out = Output { format: cli.format() };
+    if !out.is_human() {
+        colored::control::set_override(false);
+    }
 
-    match &cli.command {
-        Commands::Search { query, pacman_only, aur_only } => {
-            search_packages(query, *pacman_only, *aur_only).await?
+    // Keep pacman's and makepkg's output out of machine-readable results
+    let runner = SystemRunner { stdout_to_stderr: !out.is_human() };
+    let result = match cli.config() {
+        Ok(config) => run(&Context::from_config(config).with_runner(runner), &cli, out).await,
+        Err(e) => Err(e),
+    };
+
+    if let Err(e) = result {
+        match out.format {
+            Format::Human => eprintln!("❌ {}", e),
+            Format::Json => out.json("error", &ui::ErrorReport::new(&e)),
+            Format::Plain => eprintln!("error: {}", e),
         }
-        Commands::Install { packages, cascade } => {
-            for pkg in packages {
-                install_package(pkg, *cascade).await?;
-            }
+        std::process::exit(e.exit_code());
+    }
+}
+
+async fn run(ctx: &Context, cli: &Cli, out: Output) -> Result<()> {
+    let Some(command) = &cli.command else {
+        return interactive_install(ctx, out, &cli.query.join(" ")).await;
+    };
+
+    match command {
+        Commands::Search { query, pacman_only, aur_only, by, limit } => {
+            let limit = limit.unwrap_or(ctx.config().search_limit);
+            search_packages(ctx, out, query, *by, limit, *pacman_only, *aur_only).await?
         }
-        Commands::Remove { packages, purge } => {
-            for pkg in packages {
-                remove_package(pkg, *purge)?;
-            }
+        Commands::Info { packages } => package_info(ctx, out, packages).await?,
+        Commands::Install { packages, cascade } => install_packages(ctx, out, packages, *cascade).await?,
+        Commands::Build { packages, output } => build_packages(ctx, out, packages, output).await?,
+        Commands::Remove { packages, purge } => remove_packages(ctx, out, packages, *purge)?,
+        Commands::Update { full } => {
+            update_database(ctx, out, *full)?;
+            out.json("update", &ui::UpdateReport { full: *full });
         }
-        Commands::Update { full } => update_database(*full)?,
-        Commands::Upgrade { full } => upgrade_system(*full).await?,
+        Commands::Upgrade { full } => upgrade_system(ctx, out, *full).await?,
+        Commands::Clean { policy } => clean_cache(ctx, out, *policy)?,
+        Commands::Config { action } => configure(ctx.config(), out, action)?,
     }
 
     Ok(())
@@ -95,193 +263,322 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
 // ======================
 // Search: Pacman + AUR
 // ======================
-async fn search_packages(query: &str, pacman_only: bool, aur_only: bool) -> Result<(), Box<dyn std::error::Error>> {
-    println!("🔍 Searching for '{}'...", query.blue());
-
-    if !aur_only {
-        // 1️⃣ Offizielle Repos
-        let pacman_output = Command::new("pacman")
-            .args(&["-Ss", query])
-            .output()?;
-
-        if !pacman_output.stdout.is_empty() {
-            println!("📦 Found in official repos:");
-            let stdout = String::from_utf8_lossy(&pacman_output.stdout);
-            for line in stdout.lines().take(10) {
-                println!("  {}", line.green());
+async fn search_packages(
+    ctx: &Context,
+    out: Output,
+    query: &str,
+    by: SearchBy,
+    limit: usize,
+    pacman_only: bool,
+    aur_only: bool,
+) -> Result<()> {
+    out.human(format!("🔍 Searching for '{}' by {}...", query.blue(), by));
+    let report = resolve::search(ctx, query, by, !aur_only, !pacman_only).await?;
+
+    if out.is_human() {
+        if !aur_only {
+            match &report.repo {
+                None => write_line(format!("⚠️ Official repos cannot be searched by {}", by)),
+                Some(results) if results.is_empty() => write_line("⚠️ Not found in official repos"),
+                Some(results) => {
+                    write_line("📦 Found in official repos:");
+                    ui::print_repo_results(results, limit);
+                }
             }
-        } else {
-            println!("⚠️ Not found in official repos");
         }
-    }
-
-    if !pacman_only {
-        // 2️⃣ AUR
-        let url = format!("https://aur.archlinux.org/rpc/?v=5&type=search&arg={}", query);
-        let resp = reqwest::get(&url).await?.json::<AurResponse>().await?;
-
-        if resp.resultcount > 0 {
-            println!("🌐 Found {} packages in AUR:", resp.resultcount);
-            let mut table = Table::new();
-            table.add_row(Row::new(vec![
-                Cell::new("Name"),
-                Cell::new("Version"),
-                Cell::new("Description"),
-            ]));
-
-            for pkg in resp.results.iter().take(10) {
-                table.add_row(Row::new(vec![
-                    Cell::new(&pkg.name.green().to_string()),
-                    Cell::new(&pkg.version.yellow().to_string()),
-                    Cell::new(&pkg.description.clone().unwrap_or_else(|| "No description".into())),
-                ]));
+        match &report.aur {
+            None => {}
+            Some(results) if results.is_empty() => write_line("❌ No packages found in AUR"),
+            Some(results) => {
+                write_line(format!("🌐 Found {} packages in AUR:", results.len()));
+                ui::print_aur_results(results, limit);
             }
-
-            table.printstd();
-        } else {
-            println!("❌ No packages found in AUR");
         }
     }
 
+    out.json("search", &report);
+    for pkg in report.repo.iter().flatten() {
+        out.plain(format!("{}\t{}\t{}\t{}", pkg.repo, pkg.name, pkg.version, pkg.description));
+    }
+    for pkg in report.aur.iter().flatten() {
+        let description = pkg.description.as_deref().unwrap_or_default();
+        out.plain(format!("aur\t{}\t{}\t{}", pkg.name, pkg.version, description));
+    }
+
     Ok(())
 }
 
 // ======================
-// Install: Pacman first, then AUR
+// Package info
 // ======================
-async fn install_package(pkgname: &str, cascade: bool) -> Result<(), Box<dyn std::error::Error>> {
-    // Prüfen, ob Paket im offiziellen Repo existiert
-    let pacman_check = Command::new("pacman")
-        .args(&["-Ss", pkgname])
-        .output()?;
-
-    if !pacman_check.stdout.is_empty() {
-        println!("📦 Installing '{}' from official repos", pkgname.green());
-        let status = Command::new("sudo")
-            .arg("pacman")
-            .args(&["-S", pkgname, "--noconfirm"])
-            .status()?;
-
-        if status.success() {
-            println!("✅ Installed '{}' from official repos", pkgname.green());
-        } else {
-            println!("❌ Failed to install '{}' from official repos", pkgname.red());
+async fn package_info(ctx: &Context, out: Output, packages: &[String]) -> Result<()> {
+    let packages = resolve::package_info(ctx, packages).await?;
+
+    for info in &packages {
+        if out.is_human() {
+            ui::print_package_info(ctx.aur(), info);
         }
-        return Ok(());
+
+        let (source, version) = match (&info.repo, &info.aur, &info.local) {
+            (Some(repo), _, _) => (repo.repository.as_deref().unwrap_or_default(), repo.version.as_str()),
+            (None, Some(aur), _) => ("aur", aur.version.as_str()),
+            (None, None, Some(local)) => ("local", local.version.as_str()),
+            (None, None, None) => continue,
+        };
+        out.plain(format!("{}\t{}\t{}\t{}", source, info.name, version, info.installed_version().unwrap_or("-")));
     }
 
-    // Wenn nicht vorhanden, AUR-Build
-    println!("🌐 '{}' not found in official repos, building from AUR", pkgname.yellow());
+    out.json("info", &ui::InfoReport { packages });
+    Ok(())
+}
 
-    let home_dir = env::var("HOME").unwrap_or("/tmp".to_string());
-    let cache_dir = format!("{}/.cache/raur", home_dir);
-    if !Path::new(&cache_dir).exists() {
-        std::fs::create_dir_all(&cache_dir)?;
-    }
-    let temp_dir = format!("{}/{}", cache_dir, pkgname);
-    if Path::new(&temp_dir).exists() {
-        std::fs::remove_dir_all(&temp_dir)?;
+// ======================
+// Interactive search + install
+// ======================
+async fn interactive_install(ctx: &Context, out: Output, query: &str) -> Result<()> {
+    if !out.is_human() {
+        return Err(RaurError::Other("the interactive menu needs --format human, use 'raur search' instead".into()));
     }
 
-    let status = Command::new("git")
-        .args(&["clone", &format!("https://aur.archlinux.org/{}.git", pkgname), &temp_dir])
-        .status()?;
-    if !status.success() {
-        eprintln!("❌ Git clone failed");
+    let entries = resolve::menu_entries(ctx, query).await?;
+    if entries.is_empty() {
+        write_line(format!("❌ No packages found for '{}'", query.red()));
         return Ok(());
     }
 
-    let pb = ProgressBar::new_spinner();
-    pb.set_style(
-        ProgressStyle::default_spinner()
-            .template("{spinner} {msg}")
-            .unwrap()
-            .tick_strings(&["⠁","⠂","⠄","⡀","⢀","⠠","⠐","⠈"])
-    );
-    pb.enable_steady_tick(Duration::from_millis(100));
-    pb.set_message("Building package...");
+    let packages = ui::select_packages(ctx, &entries)?;
+    if packages.is_empty() {
+        write_line("Nothing to install");
+        return Ok(());
+    }
 
-    let makepkg_args = if cascade { vec!["-sci", "--noconfirm"] } else { vec!["-si", "--noconfirm"] };
+    install_packages(ctx, out, &packages, false).await
+}
 
-    let status = Command::new("makepkg")
-        .current_dir(&temp_dir)
-        .args(&makepkg_args)
-        .status()?;
-    pb.finish_and_clear();
+// ======================
+// Install: Pacman first, then AUR
+// ======================
+async fn install_packages(ctx: &Context, out: Output, packages: &[String], cascade: bool) -> Result<()> {
+    let report = build::install(ctx, packages, cascade).await?;
 
-    if status.success() {
-        println!("✅ Installed '{}' from AUR", pkgname.green());
-    } else {
-        println!("❌ Failed to install '{}' from AUR", pkgname.red());
+    if !report.repo.is_empty() {
+        for repo in &report.repo {
+            if !repo.target.ends_with(&format!("/{}", repo.name)) && repo.target != repo.name {
+                out.human(format!("📦 '{}' is provided by '{}'", repo.name, repo.target.green()));
+            }
+        }
+        let targets: Vec<&str> = report.repo.iter().map(|repo| repo.target.as_str()).collect();
+        out.human(format!("✅ Installed from official repos: {}", targets.join(" ").green()));
+    }
+    if let Some(aur) = &report.aur {
+        print_aur_install(out, aur);
     }
 
+    out.json("install", &report);
+    for repo in &report.repo {
+        out.plain(&repo.target);
+    }
+    for pkg in report.aur.iter().flat_map(|aur| &aur.built) {
+        out.plain(format!("aur/{}", pkg.name));
+    }
     Ok(())
 }
 
+fn print_aur_install(out: Output, install: &AurInstall) {
+    if !install.repo_deps.is_empty() {
+        out.human(format!("📦 Installed dependencies from official repos: {}", install.repo_deps.join(" ").green()));
+    }
+    for pkg in &install.built {
+        if !pkg.rebuilt {
+            out.human(format!("✅ '{}' was already built", pkg.name.green()));
+        }
+    }
+    let names: Vec<&str> = install.built.iter().map(|pkg| pkg.name.as_str()).collect();
+    out.human(format!("✅ Installed {} from AUR", names.join(", ").green()));
+    warn_unmarked(&install.unmarked);
+    warn_srcinfo(&build::srcinfo_warnings(&install.built));
+}
+
+/// Only the install reason of these is off, so this is not an error.
+fn warn_unmarked(unmarked: &[String]) {
+    if !unmarked.is_empty() {
+        eprintln!("⚠️ Could not mark {} as dependencies", unmarked.join(", "));
+    }
+}
+
+/// Skipped `.SRCINFO` lines did not keep the build from working.
+fn warn_srcinfo(warnings: &[String]) {
+    for warning in warnings {
+        eprintln!("⚠️ {}", warning);
+    }
+}
+
 // ======================
-// Remove / Purge
+// Build only
 // ======================
-fn remove_package(pkgname: &str, purge: bool) -> Result<(), Box<dyn std::error::Error>> {
-    print!("⚠️  Are you sure you want to remove '{}'? [y/N]: ", pkgname);
-    stdout().flush()?;
-    let mut input = String::new();
-    stdin().read_line(&mut input)?;
-    if input.trim().to_lowercase() != "y" {
-        println!("Aborted");
-        return Ok(());
+async fn build_packages(ctx: &Context, out: Output, packages: &[String], output: &Path) -> Result<()> {
+    out.human(format!("🌐 Building from AUR: {}", packages.join(" ").yellow()));
+    let report = build::build_aur(ctx, packages, output).await?;
+
+    if !report.repo_deps.is_empty() {
+        out.human(format!("📦 Installed dependencies from official repos: {}", report.repo_deps.join(" ").green()));
+    }
+    if !report.installed.is_empty() {
+        out.human(format!("📦 Installed AUR build dependencies: {}", report.installed.join(" ").green()));
+    }
+    warn_unmarked(&report.unmarked);
+    warn_srcinfo(&report.srcinfo_warnings);
+    for pkg in &report.packages {
+        out.human(format!("  {} {} {}", pkg.file.green(), pkg.sha256.dimmed(), if pkg.requested { "" } else { "(dependency)" }));
+        out.plain(format!("{}\t{}\t{}\t{}", pkg.name, pkg.version, pkg.file, pkg.sha256));
     }
+    out.human(format!(
+        "✅ Copied {} package file(s) to {} (see {})",
+        report.packages.len(),
+        output.display(),
+        build::MANIFEST
+    ));
+
+    out.json("build", &report);
+    Ok(())
+}
 
-    let args = if purge { vec!["-Rns", pkgname, "--noconfirm"] } else { vec!["-Rs", pkgname, "--noconfirm"] };
+// ======================
+// Clean cache
+// ======================
+fn clean_cache(ctx: &Context, out: Output, policy: CleanPolicy) -> Result<()> {
+    let report = build::clean(ctx, policy)?;
 
-    let status = Command::new("sudo")
-        .arg("pacman")
-        .args(&args)
-        .status()?;
+    for name in &report.failed {
+        out.human(format!("⚠️ Could not clean '{}'", name.yellow()));
+    }
+    match policy {
+        CleanPolicy::All => out.human(format!("🧹 Removed {} clone(s)", report.cleaned.len())),
+        CleanPolicy::KeepInstalled => {
+            for name in &report.cleaned {
+                out.human(format!("  {}", name.red()));
+            }
+            out.human(format!("🧹 Removed {} clone(s) of packages that are not installed", report.cleaned.len()));
+        }
+        CleanPolicy::Artifacts => out.human(format!("🧹 Removed build artifacts from {} clone(s)", report.cleaned.len())),
+    }
 
-    if status.success() {
-        println!("✅ Removed '{}'", pkgname.green());
-    } else {
-        println!("❌ Failed to remove '{}'", pkgname.red());
+    for name in &report.cleaned {
+        out.plain(name);
     }
+    out.json("clean", &ui::CleanSummary { policy, report });
+    Ok(())
+}
+
+// ======================
+// Remove / Purge
+// ======================
+fn remove_packages(ctx: &Context, out: Output, packages: &[String], purge: bool) -> Result<()> {
+    let report = pacman::remove_packages(ctx, packages, purge)?;
 
+    for pkgname in &report.removed {
+        out.human(format!("✅ Removed '{}'", pkgname.green()));
+        out.plain(pkgname);
+    }
+    out.json("remove", &report);
     Ok(())
 }
 
 // ======================
 // Update / Sync
 // ======================
-fn update_database(full: bool) -> Result<(), Box<dyn std::error::Error>> {
-    let pacman_args = if full { vec!["-Syy"] } else { vec!["-Sy"] };
+fn update_database(ctx: &Context, out: Output, full: bool) -> Result<()> {
+    pacman::sync_databases(ctx, full)?;
+    out.human("✅ Database synced successfully");
+    Ok(())
+}
 
-    let status = Command::new("sudo")
-        .arg("pacman")
-        .args(&pacman_args)
-        .status()?;
+// ======================
+// Upgrade
+// ======================
+async fn upgrade_system(ctx: &Context, out: Output, full: bool) -> Result<()> {
+    let report = build::upgrade(ctx, full).await?;
+    out.human("✅ System upgraded successfully");
 
-    if status.success() {
-        println!("✅ Database synced successfully");
-    } else {
-        println!("❌ Database sync failed");
+    let check = &report.aur;
+    for name in &check.not_in_aur {
+        out.human(format!("⚠️ '{}' is not in the AUR", name.yellow()));
     }
+    for upgrade in &check.ignored {
+        out.human(format!(
+            "⚠️ Ignoring the upgrade of '{}' ({} -> {})",
+            upgrade.name.yellow(),
+            upgrade.local_version,
+            upgrade.aur_version
+        ));
+    }
+    for upgrade in &check.outdated {
+        out.plain(format!("{}\t{}\t{}", upgrade.name, upgrade.local_version, upgrade.aur_version));
+    }
+
+    match &report.installed {
+        None => out.human("✅ AUR packages are up to date"),
+        Some(installed) => {
+            out.human(format!("🌐 Upgraded {} AUR package(s):", check.outdated.len()));
+            for upgrade in &check.outdated {
+                out.human(format!(
+                    "  {} {} -> {}",
+                    upgrade.name.green(),
+                    upgrade.local_version.red(),
+                    upgrade.aur_version.yellow()
+                ));
+            }
+            print_aur_install(out, installed);
+        }
+    }
+
+    out.json("upgrade", &report);
     Ok(())
 }
 
 // ======================
-// Upgrade
+// Config
 // ======================
-async fn upgrade_system(full: bool) -> Result<(), Box<dyn std::error::Error>> {
-    update_database(full)?;
+fn configure(config: &Config, out: Output, action: &ConfigAction) -> Result<()> {
+    let keys = match action {
+        ConfigAction::Get { key } => vec![key.as_str()],
+        ConfigAction::List => config::KEYS.to_vec(),
+        ConfigAction::Set { key, value, system } => return set_config(out, key, value, *system),
+    };
+
+    let mut settings = Vec::new();
+    for key in keys {
+        settings.push(ui::Setting {
+            key: key.to_string(),
+            value: config.get(key)?,
+            source: config.source(key),
+        });
+    }
 
-    let status = Command::new("sudo")
-        .arg("pacman")
-        .args(&["-Syu", "--noconfirm"])
-        .status()?;
+    for setting in &settings {
+        match action {
+            ConfigAction::Get { .. } if out.is_human() => write_line(&setting.value),
+            _ => out.human(format!("{} = {} {}", setting.key.green(), setting.value, format!("({})", setting.source).dimmed())),
+        }
+        out.plain(format!("{}\t{}\t{}", setting.key, setting.value, setting.source));
+    }
+    out.json("config", &ui::ConfigReport { settings });
+    Ok(())
+}
 
-    if status.success() {
-        println!("✅ System upgraded successfully");
+fn set_config(out: Output, key: &str, value: &str, system: bool) -> Result<()> {
+    let file = if system {
+        config::SYSTEM_CONFIG.into()
     } else {
-        println!("❌ Upgrade failed");
-    }
+        config::user_config_path()
+            .ok_or_else(|| RaurError::Config("no user config: HOME is not set, use --system".into()))?
+    };
+
+    config::write_setting(&file, key, value)?;
 
+    let file = file.display().to_string();
+    out.human(format!("✅ Set {} = {} in {}", key.green(), value, file));
+    out.plain(format!("{}\t{}\t{}", key, value, file));
+    out.json("config-set", &ui::ConfigUpdate { key: key.to_string(), value: value.to_string(), file });
     Ok(())
 }
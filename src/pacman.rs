@@ -0,0 +1,325 @@
+//! Queries against the local and sync databases and pacman transactions.
+//!
+//! Queries read pacman's databases directly (see [`crate::db`]);
+//! transactions shell out to pacman through the context's runner.
+
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+
+use serde::Serialize;
+
+use crate::aur::SearchBy;
+use crate::db::{LocalDb, SyncDbs, SyncPackage};
+use crate::error::{CommandFailure, RaurError, Result};
+use crate::pacman_conf::PACMAN_CONF;
+use crate::resolve::dep_name;
+use crate::runner::Cmd;
+use crate::ui::{self, RemoveReport};
+use crate::Context;
+
+/// pacman run as root through the configured escalation backend, pointed
+/// at the same pacman.conf and database raur read.
+pub fn root_pacman(ctx: &Context) -> Cmd {
+    let config = ctx.config();
+    let mut cmd = config.escalation.command("pacman");
+    if config.pacman_conf.as_path() != Path::new(PACMAN_CONF) {
+        cmd = cmd.arg("--config").arg(&config.pacman_conf);
+    }
+    if let Some(dbpath) = &config.dbpath {
+        cmd = cmd.arg("--dbpath").arg(dbpath);
+    }
+    cmd
+}
+
+/// `--noconfirm` unless the config asks for pacman's own prompts.
+pub(crate) fn noconfirm(ctx: &Context) -> Option<&'static str> {
+    ctx.config().noconfirm.then_some("--noconfirm")
+}
+
+// ======================
+// Sync database queries
+// ======================
+/// A search result; AUR results use "aur" as their repo.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct RepoPackage {
+    pub repo: String,
+    pub name: String,
+    pub version: String,
+    pub description: String,
+    pub installed: bool,
+}
+
+/// Search the sync databases by name, description and provides with a
+/// regex, like `pacman -Ss`.
+pub fn search(ctx: &Context, query: &str) -> Result<Vec<RepoPackage>> {
+    let dbs = ctx.sync_dbs()?;
+    let local = LocalDb::load(ctx)?;
+    Ok(dbs.search(query, true)?.into_iter().map(|pkg| repo_package(pkg, &local)).collect())
+}
+
+/// Search the sync databases by one package field. Returns `None` for the
+/// AUR-only fields (maintainer, keywords).
+pub fn search_by(ctx: &Context, query: &str, by: SearchBy) -> Result<Option<Vec<RepoPackage>>> {
+    let dbs = match by {
+        SearchBy::Maintainer | SearchBy::Keywords => return Ok(None),
+        _ => ctx.sync_dbs()?,
+    };
+    let results = match by {
+        SearchBy::Name => dbs.search(query, false)?,
+        SearchBy::Depends => dependents(&dbs, query, |pkg| &pkg.depends),
+        SearchBy::Makedepends => dependents(&dbs, query, |pkg| &pkg.makedepends),
+        SearchBy::Provides => dbs.packages().filter(|pkg| pkg.name == query || pkg.provides(query)).collect(),
+        _ => dbs.search(query, true)?,
+    };
+
+    let local = LocalDb::load(ctx)?;
+    Ok(Some(results.into_iter().map(|pkg| repo_package(pkg, &local)).collect()))
+}
+
+/// Packages with a dependency in `field` on `name`. When a sync package is
+/// called `name`, that is every dependency it satisfies, by version or
+/// through its provides ("Required By" of `pacman -Sii`); otherwise, e.g.
+/// for a name only provided, every dependency on the name.
+fn dependents<'a>(
+    dbs: &'a SyncDbs,
+    name: &str,
+    field: impl Fn(&SyncPackage) -> &Vec<String>,
+) -> Vec<&'a SyncPackage> {
+    let target = dbs.package(name);
+    let depends_on = |dep: &String| match target {
+        Some(target) => target.satisfies(dep),
+        None => dep_name(dep) == name,
+    };
+    dbs.packages().filter(|pkg| field(pkg).iter().any(depends_on)).collect()
+}
+
+fn repo_package(pkg: &SyncPackage, local: &LocalDb) -> RepoPackage {
+    RepoPackage {
+        repo: pkg.repo.clone(),
+        name: pkg.name.clone(),
+        version: pkg.version.clone(),
+        description: pkg.description.clone(),
+        installed: local.is_installed(&pkg.name),
+    }
+}
+
+/// What a requested name matched in the sync databases.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RepoMatch {
+    /// A package as `repo/name`
+    Package(String),
+    /// Several packages providing the name, as `repo/name`
+    Providers(Vec<String>),
+    /// A package group
+    Group(String),
+}
+
+/// Look a name up in the sync databases: exact package name first, then
+/// packages providing it, then package groups. A `repo/name` only matches
+/// that package of that repo.
+pub fn find(ctx: &Context, name: &str) -> Result<Option<RepoMatch>> {
+    let dbs = ctx.sync_dbs()?;
+    if let Some((repo, pkgname)) = name.split_once('/') {
+        let pkg = dbs.dbs().iter().filter(|db| db.name == repo).find_map(|db| db.package(pkgname));
+        return Ok(pkg.map(|pkg| RepoMatch::Package(format!("{}/{}", pkg.repo, pkg.name))));
+    }
+
+    if let Some(pkg) = dbs.package(name) {
+        return Ok(Some(RepoMatch::Package(format!("{}/{}", pkg.repo, pkg.name))));
+    }
+
+    let mut providers = providers(ctx, name)?;
+    match providers.len() {
+        0 => {}
+        1 => return Ok(providers.pop().map(RepoMatch::Package)),
+        _ => return Ok(Some(RepoMatch::Providers(providers))),
+    }
+
+    if !dbs.group(name).is_empty() {
+        return Ok(Some(RepoMatch::Group(name.to_string())));
+    }
+
+    Ok(None)
+}
+
+/// All sync packages whose `Provides` contain `name`, as `repo/name`.
+fn providers(ctx: &Context, name: &str) -> Result<Vec<String>> {
+    Ok(ctx
+        .sync_dbs()?
+        .providers(name)
+        .into_iter()
+        .map(|pkg| format!("{}/{}", pkg.repo, pkg.name))
+        .collect())
+}
+
+/// The official repo package that satisfies the dependency, if any.
+pub fn sync_provider(ctx: &Context, dep: &str) -> Result<Option<String>> {
+    Ok(ctx.sync_dbs()?.satisfier(dep).map(|pkg| pkg.name.clone()))
+}
+
+/// A package record with the fields `pacman -Si` or `pacman -Qi` prints.
+/// Fields a database does not have stay empty, e.g. `repository` for
+/// installed packages.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct RepoInfo {
+    pub repository: Option<String>,
+    pub name: String,
+    pub version: String,
+    pub description: String,
+    pub url: String,
+    pub licenses: Vec<String>,
+    pub groups: Vec<String>,
+    pub provides: Vec<String>,
+    pub depends: Vec<String>,
+    /// `name: reason` entries
+    pub optional_deps: Vec<String>,
+    pub conflicts: Vec<String>,
+    pub replaces: Vec<String>,
+    pub download_size: Option<String>,
+    pub installed_size: String,
+    pub packager: String,
+    /// Unix timestamp
+    pub build_date: Option<i64>,
+    /// Unix timestamp
+    pub install_date: Option<i64>,
+    pub install_reason: Option<String>,
+}
+
+/// Sync database records of the given packages. Unknown names are left out.
+pub fn sync_info<S: AsRef<str>>(ctx: &Context, names: &[S]) -> Result<Vec<RepoInfo>> {
+    if names.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let dbs = ctx.sync_dbs()?;
+    Ok(names
+        .iter()
+        .filter_map(|name| dbs.package(name.as_ref()))
+        .map(|pkg| pkg.to_info())
+        .collect())
+}
+
+// ======================
+// Local database queries
+// ======================
+/// Local database records of the given packages. Packages that are not
+/// installed are left out.
+pub fn local_info<S: AsRef<str>>(ctx: &Context, names: &[S]) -> Result<Vec<RepoInfo>> {
+    if names.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let local = LocalDb::load(ctx)?;
+    Ok(names
+        .iter()
+        .filter_map(|name| local.package(name.as_ref()))
+        .map(|pkg| pkg.to_info())
+        .collect())
+}
+
+/// Which of the given packages are installed locally.
+pub fn installed<'a>(ctx: &Context, names: impl Iterator<Item = &'a str>) -> Result<HashSet<String>> {
+    let names: Vec<&str> = names.collect();
+    if names.is_empty() {
+        return Ok(HashSet::new());
+    }
+
+    let local = LocalDb::load(ctx)?;
+    Ok(names
+        .into_iter()
+        .filter(|name| local.is_installed(name))
+        .map(|name| name.to_string())
+        .collect())
+}
+
+/// Installed packages that are not in any sync database, as
+/// `(name, version)` like `pacman -Qm`.
+pub fn foreign(ctx: &Context) -> Result<Vec<(String, String)>> {
+    let dbs = ctx.sync_dbs()?;
+    Ok(LocalDb::load(ctx)?
+        .packages()
+        .filter(|pkg| dbs.package(&pkg.name).is_none())
+        .map(|pkg| (pkg.name.clone(), pkg.version.clone()))
+        .collect())
+}
+
+/// Whether an installed package already satisfies the dependency.
+pub fn is_satisfied(ctx: &Context, dep: &str) -> Result<bool> {
+    Ok(LocalDb::load(ctx)?.satisfier(dep).is_some())
+}
+
+// ======================
+// Transactions
+// ======================
+/// Install repo targets (`repo/name` or group names) in one transaction.
+pub fn install(ctx: &Context, targets: &[String]) -> Result<()> {
+    transaction(ctx, root_pacman(ctx).arg("-S").args(targets).args(noconfirm(ctx)))
+}
+
+/// Install missing dependencies from the repos, marked as dependencies.
+pub fn install_deps(ctx: &Context, deps: &[String]) -> Result<()> {
+    transaction(ctx, root_pacman(ctx).args(["-S", "--needed", "--asdeps"]).args(noconfirm(ctx)).args(deps))
+}
+
+/// Install built package files with a single `pacman -U`.
+pub fn install_files(ctx: &Context, files: &[PathBuf]) -> Result<()> {
+    transaction(ctx, root_pacman(ctx).arg("-U").args(noconfirm(ctx)).args(files))
+}
+
+/// Mark installed packages as dependencies. Returns whether pacman succeeded.
+pub fn mark_as_deps(ctx: &Context, names: &[&str]) -> Result<bool> {
+    let output = ctx.runner().output(&root_pacman(ctx).args(["-D", "--asdeps"]).args(names))?;
+    Ok(output.success())
+}
+
+/// Remove packages one by one after asking as the `remove` policy says.
+/// Stops with [`RaurError::UserAbort`] at the first package that may not be
+/// removed; the ones before it stay removed.
+pub fn remove_packages(ctx: &Context, packages: &[String], purge: bool) -> Result<RemoveReport> {
+    let mut removed = Vec::new();
+    for pkgname in packages {
+        if !ui::confirm_removal(ctx, pkgname)? {
+            return Err(RaurError::UserAbort(format!("removal of '{}' aborted", pkgname)));
+        }
+        remove(ctx, pkgname, purge)?;
+        removed.push(pkgname.clone());
+    }
+
+    Ok(RemoveReport { removed, purge })
+}
+
+/// Remove a package with its unneeded dependencies; `purge` also drops
+/// configuration files.
+pub fn remove(ctx: &Context, pkgname: &str, purge: bool) -> Result<()> {
+    let flags = if purge { "-Rns" } else { "-Rs" };
+    transaction(ctx, root_pacman(ctx).args([flags, pkgname]).args(noconfirm(ctx)))
+}
+
+/// Refresh the sync databases; `full` forces a download even if they are up to date.
+pub fn sync_databases(ctx: &Context, full: bool) -> Result<()> {
+    ctx.forget_sync_dbs();
+    transaction(ctx, root_pacman(ctx).arg(if full { "-Syy" } else { "-Sy" }))
+}
+
+/// Upgrade all repo packages.
+pub fn upgrade(ctx: &Context) -> Result<()> {
+    ctx.forget_sync_dbs();
+    transaction(ctx, root_pacman(ctx).arg("-Syu").args(noconfirm(ctx)))
+}
+
+fn transaction(ctx: &Context, cmd: Cmd) -> Result<()> {
+    if !ctx.config().noconfirm {
+        ctx.ensure_interactive("pacman's questions")?;
+    }
+    let status = ctx.runner().status(&cmd)?;
+    if status.success() {
+        return Ok(());
+    }
+
+    let failure = CommandFailure::new(&cmd, &status);
+    // sudo itself failing (wrong password, not in sudoers) is not a pacman error
+    if ctx.config().escalation.refused(&status) {
+        return Err(RaurError::Permission(failure.to_string()));
+    }
+    Err(RaurError::Pacman(failure))
+}
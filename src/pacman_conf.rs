@@ -0,0 +1,238 @@
+//! pacman's configuration, `/etc/pacman.conf`.
+//!
+//! Only what raur needs is kept: the `[options]` that decide where the
+//! databases are and which packages to leave alone, and the repos in the
+//! order pacman searches them. `Include` values are used as written, like
+//! pacman does: relative paths are relative to the working directory, not to
+//! the including file, and any path component may be a glob pattern. Other
+//! directives are accepted and ignored.
+
+use std::path::{Component, Path, PathBuf};
+
+use crate::error::{RaurError, Result};
+
+pub const PACMAN_CONF: &str = "/etc/pacman.conf";
+
+/// pacman gives up on deeper `Include` chains as well.
+const MAX_INCLUDE_DEPTH: usize = 10;
+
+/// A repo section, e.g. `[core]`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Repo {
+    pub name: String,
+    /// `Server` lines, directly or from included mirror lists
+    pub servers: Vec<String>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PacmanConf {
+    /// `DBPath`, `None` for pacman's default
+    pub db_path: Option<PathBuf>,
+    /// `CacheDir`, pacman's package cache
+    pub cache_dirs: Vec<PathBuf>,
+    /// `Architecture`, `auto` standing for the machine's
+    pub architecture: Vec<String>,
+    /// `IgnorePkg`, may hold glob patterns
+    pub ignore_pkg: Vec<String>,
+    /// `IgnoreGroup`, may hold glob patterns
+    pub ignore_group: Vec<String>,
+    /// In the order pacman uses them
+    pub repos: Vec<Repo>,
+}
+
+impl PacmanConf {
+    pub fn load(path: &Path) -> Result<PacmanConf> {
+        let mut conf = PacmanConf::default();
+        let mut section = None;
+        conf.read(path, &mut section, 0)?;
+        Ok(conf)
+    }
+
+    pub fn repo_names(&self) -> Vec<&str> {
+        self.repos.iter().map(|repo| repo.name.as_str()).collect()
+    }
+
+    /// Whether pacman leaves a package alone on upgrades, by its name or
+    /// one of its groups.
+    pub fn is_ignored(&self, name: &str, groups: &[String]) -> bool {
+        self.ignore_pkg.iter().any(|pattern| glob_match(pattern, name))
+            || self
+                .ignore_group
+                .iter()
+                .any(|pattern| groups.iter().any(|group| glob_match(pattern, group)))
+    }
+
+    /// `section` is the repo being read, `Some("options")` for
+    /// `[options]`, and carries over into included files.
+    fn read(&mut self, path: &Path, section: &mut Option<String>, depth: usize) -> Result<()> {
+        let text = std::fs::read_to_string(path).map_err(|e| {
+            RaurError::Config(format!("cannot read {}: {}", path.display(), e))
+        })?;
+
+        for (i, line) in text.lines().enumerate() {
+            let invalid = |message: String| RaurError::PacmanConf {
+                path: path.to_path_buf(),
+                line: i + 1,
+                message,
+            };
+
+            let line = line.split('#').next().unwrap_or_default().trim();
+            if line.is_empty() {
+                continue;
+            }
+
+            if let Some(name) = line.strip_prefix('[') {
+                let name = name
+                    .strip_suffix(']')
+                    .filter(|name| !name.is_empty())
+                    .ok_or_else(|| invalid(format!("invalid section '{}'", line)))?;
+                if name == "local" {
+                    return Err(invalid("'local' is reserved and cannot be used as a repo name".into()));
+                }
+                if name != "options" {
+                    self.repos.push(Repo {
+                        name: name.to_string(),
+                        servers: Vec::new(),
+                    });
+                }
+                *section = Some(name.to_string());
+                continue;
+            }
+
+            let (key, value) = match line.split_once('=') {
+                Some((key, value)) => (key.trim(), value.trim()),
+                None => (line, ""),
+            };
+            let Some(current) = section.as_deref() else {
+                return Err(invalid(format!("directive '{}' is not in a section", key)));
+            };
+
+            if key == "Include" {
+                if depth >= MAX_INCLUDE_DEPTH {
+                    return Err(invalid(format!("'{}' is included too deeply", value)));
+                }
+                for include in expand_include(value).map_err(|e| invalid(e.to_string()))? {
+                    self.read(&include, section, depth + 1)?;
+                }
+                continue;
+            }
+
+            let values = || value.split_whitespace().map(String::from);
+            match (current, key) {
+                ("options", "DBPath") => self.db_path = Some(PathBuf::from(value)),
+                ("options", "CacheDir") => self.cache_dirs.extend(values().map(PathBuf::from)),
+                ("options", "Architecture") => self.architecture.extend(values()),
+                ("options", "IgnorePkg") => self.ignore_pkg.extend(values()),
+                ("options", "IgnoreGroup") => self.ignore_group.extend(values()),
+                ("options", _) => {}
+                (_, "Server") => {
+                    if let Some(repo) = self.repos.iter_mut().rev().find(|repo| repo.name == current) {
+                        repo.servers.push(value.to_string());
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// The files an `Include` value names, sorted. Every path component may be
+/// a glob pattern, and like pacman's `glob()` a value that matches nothing,
+/// pattern or not, includes nothing.
+fn expand_include(value: &str) -> std::io::Result<Vec<PathBuf>> {
+    let mut paths = vec![PathBuf::new()];
+    for component in Path::new(value).components() {
+        let pattern = component.as_os_str().to_string_lossy();
+        if !matches!(component, Component::Normal(_)) || !pattern.contains(['*', '?', '[']) {
+            paths.iter_mut().for_each(|path| path.push(component));
+            continue;
+        }
+
+        let mut matches = Vec::new();
+        for dir in &paths {
+            let read = if dir.as_os_str().is_empty() { Path::new(".") } else { dir.as_path() };
+            if !read.is_dir() {
+                continue;
+            }
+            for entry in std::fs::read_dir(read)? {
+                let name = entry?.file_name();
+                let name_str = name.to_string_lossy();
+                // Like glob(), wildcards do not match a leading dot
+                if name_str.starts_with('.') && !pattern.starts_with('.') {
+                    continue;
+                }
+                if glob_match(&pattern, &name_str) {
+                    matches.push(dir.join(&name));
+                }
+            }
+        }
+        paths = matches;
+    }
+
+    let mut files: Vec<PathBuf> = paths.into_iter().filter(|path| path.is_file()).collect();
+    files.sort();
+    Ok(files)
+}
+
+/// `fnmatch` without flags: `*`, `?` and `[...]` classes (`!` or `^`
+/// negates, `a-z` ranges).
+pub fn glob_match(pattern: &str, text: &str) -> bool {
+    fn class(pattern: &[char], c: char) -> Option<(bool, usize)> {
+        let mut i = 1;
+        let negated = matches!(pattern.get(i), Some('!') | Some('^'));
+        if negated {
+            i += 1;
+        }
+        let mut matched = false;
+        let start = i;
+        while i < pattern.len() && (pattern[i] != ']' || i == start) {
+            if pattern.get(i + 1) == Some(&'-') && pattern.get(i + 2).is_some_and(|end| *end != ']') {
+                matched |= (pattern[i]..=pattern[i + 2]).contains(&c);
+                i += 3;
+            } else {
+                matched |= pattern[i] == c;
+                i += 1;
+            }
+        }
+        // An unclosed '[' is matched literally
+        (i < pattern.len()).then_some((matched != negated, i + 1))
+    }
+
+    fn matches(pattern: &[char], text: &[char]) -> bool {
+        match pattern.first() {
+            None => text.is_empty(),
+            Some('*') => (0..=text.len()).any(|skip| matches(&pattern[1..], &text[skip..])),
+            Some('?') => !text.is_empty() && matches(&pattern[1..], &text[1..]),
+            Some('[') if !text.is_empty() => match class(pattern, text[0]) {
+                Some((true, len)) => matches(&pattern[len..], &text[1..]),
+                Some((false, _)) => false,
+                None => text[0] == '[' && matches(&pattern[1..], &text[1..]),
+            },
+            Some(c) => text.first() == Some(c) && matches(&pattern[1..], &text[1..]),
+        }
+    }
+
+    let pattern: Vec<char> = pattern.chars().collect();
+    let text: Vec<char> = text.chars().collect();
+    matches(&pattern, &text)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn globs_match_like_fnmatch() {
+        assert!(glob_match("linux*", "linux-lts"));
+        assert!(glob_match("*-git", "yay-git"));
+        assert!(!glob_match("*-git", "git"));
+        assert!(glob_match("python?", "python3"));
+        assert!(glob_match("lib[0-9]*", "lib32-glibc"));
+        assert!(!glob_match("lib[!0-9]*", "lib32-glibc"));
+        assert!(glob_match("foo[", "foo["));
+        assert!(glob_match("exact", "exact"));
+        assert!(!glob_match("exact", "exactly"));
+    }
+}
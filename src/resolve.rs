@@ -0,0 +1,440 @@
+//! Target and dependency resolution across the official repos and the AUR.
+
+use std::cmp::Ordering;
+use std::collections::{HashMap, HashSet};
+
+use serde::Serialize;
+
+use crate::aur::{AurPackage, SearchBy};
+use crate::db::LocalDb;
+use crate::error::{Missing, RaurError, Result};
+use crate::pacman::{self, RepoInfo, RepoMatch, RepoPackage};
+use crate::ui::{self, SearchReport};
+use crate::version::vercmp;
+use crate::Context;
+
+/// Strip a version constraint from a dependency, e.g. `foo>=1.0` -> `foo`.
+pub fn dep_name(dep: &str) -> &str {
+    dep.split(['<', '>', '=']).next().unwrap_or(dep)
+}
+
+// ======================
+// Search
+// ======================
+/// Search the sync databases and the AUR by `by`; `repo` and `aur` pick the
+/// sources. The repo results are `None` when the sync databases do not have
+/// the field.
+pub async fn search(ctx: &Context, query: &str, by: SearchBy, repo: bool, aur: bool) -> Result<SearchReport> {
+    Ok(SearchReport {
+        query: query.to_string(),
+        by: by.to_string(),
+        repo: if repo { pacman::search_by(ctx, query, by)? } else { None },
+        aur: if aur { Some(ctx.aur().search(query, by).await?) } else { None },
+    })
+}
+
+/// Entries of the numbered install menu: repo matches like `pacman -Ss`
+/// lists them, then AUR matches by votes.
+pub async fn menu_entries(ctx: &Context, query: &str) -> Result<Vec<RepoPackage>> {
+    let mut entries = pacman::search(ctx, query)?;
+
+    let mut aur_results = ctx.aur().search(query, SearchBy::NameDesc).await?;
+    aur_results.sort_by_key(|pkg| std::cmp::Reverse(pkg.num_votes));
+    let installed = pacman::installed(ctx, aur_results.iter().map(|pkg| pkg.name.as_str()))?;
+    for pkg in aur_results {
+        entries.push(RepoPackage {
+            repo: "aur".into(),
+            installed: installed.contains(&pkg.name),
+            name: pkg.name,
+            version: pkg.version,
+            description: pkg.description.unwrap_or_default(),
+        });
+    }
+
+    Ok(entries)
+}
+
+// ======================
+// Install targets
+// ======================
+/// A requested name and what pacman installs for it, e.g. `sh` -> `core/bash`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct RepoTarget {
+    pub name: String,
+    /// `repo/name` of a package, or a group name
+    pub target: String,
+}
+
+/// Requested names split by where they will be installed from.
+#[derive(Debug, Default)]
+pub struct Targets {
+    pub repo: Vec<RepoTarget>,
+    /// Names not found in the repos, to be built from the AUR
+    pub aur: Vec<String>,
+}
+
+/// Look every name up in the sync databases; whatever is not found there is
+/// left for the AUR. Asks the user when several repo packages provide a name.
+///
+/// A `repo/name` target is installed from exactly that repo, and `aur/name`
+/// from the AUR without looking at the repos.
+pub fn split_targets(ctx: &Context, names: &[String]) -> Result<Targets> {
+    let mut targets = Targets::default();
+    for name in names {
+        if let Some(pkgname) = name.strip_prefix("aur/") {
+            targets.aur.push(pkgname.to_string());
+            continue;
+        }
+        let target = match pacman::find(ctx, name)? {
+            Some(RepoMatch::Package(id)) | Some(RepoMatch::Group(id)) => id,
+            Some(RepoMatch::Providers(providers)) => ui::choose_provider(ctx, name, &providers)?,
+            None if name.contains('/') => {
+                return Err(RaurError::NotFound(vec![Missing {
+                    name: name.clone(),
+                    required_by: None,
+                }]));
+            }
+            None => {
+                targets.aur.push(name.clone());
+                continue;
+            }
+        };
+        targets.repo.push(RepoTarget {
+            name: name.clone(),
+            target,
+        });
+    }
+
+    Ok(targets)
+}
+
+// ======================
+// Package info
+// ======================
+/// Everything known about a package name across the repos, the local
+/// database and the AUR.
+#[derive(Debug, Serialize)]
+pub struct PackageInfo {
+    pub name: String,
+    /// Sync database record
+    pub repo: Option<RepoInfo>,
+    /// AUR record with the RPC field names, only looked up when the name is
+    /// not in the repos
+    pub aur: Option<AurPackage>,
+    /// Installed package record from the local database
+    pub local: Option<RepoInfo>,
+}
+
+impl PackageInfo {
+    pub fn installed_version(&self) -> Option<&str> {
+        self.local.as_ref().map(|local| local.version.as_str())
+    }
+}
+
+/// Look the names up in the sync databases, the local database and the
+/// AUR. Names found nowhere are an error.
+pub async fn package_info(ctx: &Context, names: &[String]) -> Result<Vec<PackageInfo>> {
+    let mut repo = pacman::sync_info(ctx, names)?;
+    let mut local = pacman::local_info(ctx, names)?;
+
+    let aur_names: Vec<&str> = names
+        .iter()
+        .map(String::as_str)
+        .filter(|name| !repo.iter().any(|pkg| pkg.name == *name))
+        .collect();
+    let mut aur = if aur_names.is_empty() { Vec::new() } else { ctx.aur().info(&aur_names).await? };
+
+    let mut found = Vec::new();
+    let mut missing = Vec::new();
+    for name in names {
+        let take = |packages: &mut Vec<RepoInfo>| {
+            let idx = packages.iter().position(|pkg| &pkg.name == name)?;
+            Some(packages.swap_remove(idx))
+        };
+        let info = PackageInfo {
+            name: name.clone(),
+            repo: take(&mut repo),
+            local: take(&mut local),
+            aur: aur.iter().position(|pkg| &pkg.name == name).map(|idx| aur.swap_remove(idx)),
+        };
+
+        if info.repo.is_none() && info.aur.is_none() && info.local.is_none() {
+            missing.push(Missing {
+                name: name.clone(),
+                required_by: None,
+            });
+        } else {
+            found.push(info);
+        }
+    }
+
+    if !missing.is_empty() {
+        return Err(RaurError::NotFound(missing));
+    }
+    Ok(found)
+}
+
+// ======================
+// Dependency resolution
+// ======================
+/// AUR packages built together from one git repository (`PackageBase`).
+#[derive(Debug, Clone)]
+pub struct AurBase {
+    pub pkgbase: String,
+    /// The packages of the base that were requested or are needed, by name
+    pub packages: Vec<AurPackage>,
+}
+
+impl AurBase {
+    /// Dependencies of the wanted packages, without the ones the base
+    /// builds itself.
+    pub fn build_dependencies(&self) -> impl Iterator<Item = &String> {
+        self.packages
+            .iter()
+            .flat_map(AurPackage::build_dependencies)
+            .filter(|dep| !self.packages.iter().any(|pkg| pkg.name == dep_name(dep)))
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct Resolution {
+    /// Dependencies available in the official repos
+    pub repo: Vec<String>,
+    /// AUR package bases in build order, dependencies first
+    pub aur: Vec<AurBase>,
+    /// Targets and dependencies found nowhere
+    pub missing: Vec<Missing>,
+}
+
+/// Walk the dependency graph of the given AUR packages across the repos and
+/// the AUR. Every dependency is checked with its version constraint; an AUR
+/// package too old or too new for one of them counts as missing.
+pub async fn resolve_dependencies<S: AsRef<str>>(ctx: &Context, targets: &[S]) -> Result<Resolution> {
+    let local = LocalDb::load(ctx)?;
+    let mut found_aur: HashMap<String, AurPackage> = HashMap::new();
+    let mut repo = Vec::new();
+    let mut missing = Vec::new();
+    // Whole dependencies, so `foo>=2` is still checked after `foo`
+    let mut seen: HashSet<String> = targets.iter().map(|t| t.as_ref().to_string()).collect();
+    let mut pending: Vec<(String, Option<String>)> = targets
+        .iter()
+        .map(|t| (t.as_ref().to_string(), None))
+        .collect();
+
+    while !pending.is_empty() {
+        let mut names: Vec<&str> = pending
+            .iter()
+            .map(|(dep, _)| dep_name(dep))
+            .filter(|name| !found_aur.contains_key(*name))
+            .collect();
+        names.sort_unstable();
+        names.dedup();
+        let found = if names.is_empty() { Vec::new() } else { ctx.aur().info(&names).await? };
+
+        let mut next = Vec::new();
+        for (dep, required_by) in pending.drain(..) {
+            let name = dep_name(&dep);
+            // Found before, for another constraint on the same name
+            if let Some(pkg) = found_aur.get(name) {
+                if !pkg.satisfies(&dep) {
+                    missing.push(Missing { name: dep, required_by });
+                }
+                continue;
+            }
+            let Some(pkg) = found.iter().find(|pkg| pkg.name == name) else {
+                missing.push(Missing {
+                    name: name.to_string(),
+                    required_by,
+                });
+                continue;
+            };
+            if !pkg.satisfies(&dep) {
+                missing.push(Missing { name: dep, required_by });
+                continue;
+            }
+
+            for dep in pkg.build_dependencies() {
+                if local.satisfier(dep).is_some() || !seen.insert(dep.clone()) {
+                    continue;
+                }
+                match pacman::sync_provider(ctx, dep)? {
+                    Some(provider) if repo.contains(&provider) => {}
+                    Some(provider) => repo.push(provider),
+                    None => next.push((dep.clone(), Some(pkg.name.clone()))),
+                }
+            }
+            found_aur.insert(pkg.name.clone(), pkg.clone());
+        }
+        pending = next;
+    }
+
+    // Split packages share one clone and one build
+    let base_of: HashMap<String, String> = found_aur
+        .values()
+        .map(|pkg| (pkg.name.clone(), pkg.package_base.clone()))
+        .collect();
+    let mut bases: HashMap<String, AurBase> = HashMap::new();
+    for pkg in found_aur.into_values() {
+        bases
+            .entry(pkg.package_base.clone())
+            .or_insert_with(|| AurBase {
+                pkgbase: pkg.package_base.clone(),
+                packages: Vec::new(),
+            })
+            .packages
+            .push(pkg);
+    }
+    for base in bases.values_mut() {
+        base.packages.sort_by(|a, b| a.name.cmp(&b.name));
+    }
+
+    let order = build_order(&bases, &base_of)?;
+    let aur = order
+        .into_iter()
+        .filter_map(|pkgbase| bases.remove(&pkgbase))
+        .collect();
+
+    Ok(Resolution { repo, aur, missing })
+}
+
+/// Order package bases so that every base comes after the bases of its AUR
+/// dependencies. `base_of` maps package names to their base.
+fn build_order(bases: &HashMap<String, AurBase>, base_of: &HashMap<String, String>) -> Result<Vec<String>> {
+    fn visit<'a>(
+        name: &'a str,
+        bases: &'a HashMap<String, AurBase>,
+        base_of: &'a HashMap<String, String>,
+        done: &mut HashSet<&'a str>,
+        path: &mut Vec<&'a str>,
+        order: &mut Vec<String>,
+    ) -> Result<()> {
+        if done.contains(name) {
+            return Ok(());
+        }
+        if let Some(start) = path.iter().position(|p| *p == name) {
+            let mut cycle: Vec<String> = path[start..].iter().map(|p| p.to_string()).collect();
+            cycle.push(name.to_string());
+            return Err(RaurError::DependencyCycle(cycle));
+        }
+
+        path.push(name);
+        for dep in bases[name].build_dependencies() {
+            if let Some(dep_base) = base_of.get(dep_name(dep)) {
+                visit(dep_base, bases, base_of, done, path, order)?;
+            }
+        }
+        path.pop();
+
+        done.insert(name);
+        order.push(name.to_string());
+        Ok(())
+    }
+
+    let mut names: Vec<&String> = bases.keys().collect();
+    names.sort();
+
+    let mut done = HashSet::new();
+    let mut order = Vec::new();
+    for name in names {
+        visit(name, bases, base_of, &mut done, &mut Vec::new(), &mut order)?;
+    }
+
+    Ok(order)
+}
+
+// ======================
+// AUR upgrades
+// ======================
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct AurUpgrade {
+    pub name: String,
+    pub local_version: String,
+    pub aur_version: String,
+}
+
+/// Foreign packages compared against the AUR.
+#[derive(Debug, Default, Serialize)]
+pub struct UpgradeCheck {
+    /// Packages with a newer version in the AUR
+    pub outdated: Vec<AurUpgrade>,
+    /// Newer versions of packages pacman.conf ignores (`IgnorePkg`,
+    /// `IgnoreGroup`), left out of `outdated`
+    pub ignored: Vec<AurUpgrade>,
+    /// Foreign packages the AUR does not know
+    pub not_in_aur: Vec<String>,
+}
+
+/// Compare every foreign package with its AUR version.
+pub async fn aur_upgrades(ctx: &Context) -> Result<UpgradeCheck> {
+    let foreign = pacman::foreign(ctx)?;
+    let mut check = UpgradeCheck::default();
+    if foreign.is_empty() {
+        return Ok(check);
+    }
+
+    let names: Vec<&str> = foreign.iter().map(|(name, _)| name.as_str()).collect();
+    let remote = ctx.aur().info(&names).await?;
+    let conf = ctx.pacman_conf()?;
+    let local = LocalDb::load(ctx)?;
+
+    for (name, local_version) in foreign {
+        match remote.iter().find(|pkg| pkg.name == name) {
+            Some(pkg) => {
+                if vercmp(&local_version, &pkg.version) == Ordering::Less {
+                    let groups = local.package(&name).map(|pkg| pkg.groups.as_slice()).unwrap_or_default();
+                    let ignored = conf.is_ignored(&name, groups);
+                    let upgrade = AurUpgrade {
+                        name,
+                        local_version,
+                        aur_version: pkg.version.clone(),
+                    };
+                    if ignored {
+                        check.ignored.push(upgrade);
+                    } else {
+                        check.outdated.push(upgrade);
+                    }
+                }
+            }
+            None => check.not_in_aur.push(name),
+        }
+    }
+
+    Ok(check)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn base(name: &str, depends: &[&str]) -> (String, AurBase) {
+        let pkg = AurPackage {
+            name: name.into(),
+            package_base: name.into(),
+            depends: depends.iter().map(|dep| dep.to_string()).collect(),
+            ..AurPackage::default()
+        };
+        (name.to_string(), AurBase { pkgbase: name.into(), packages: vec![pkg] })
+    }
+
+    fn order(bases: &[(String, AurBase)]) -> Result<Vec<String>> {
+        let base_of = bases.iter().map(|(name, _)| (name.clone(), name.clone())).collect();
+        build_order(&bases.iter().cloned().collect(), &base_of)
+    }
+
+    #[test]
+    fn dependencies_are_built_first() {
+        let bases = [base("a", &["b>=1", "glibc"]), base("b", &["c"]), base("c", &[])];
+        assert_eq!(order(&bases).unwrap(), ["c", "b", "a"]);
+    }
+
+    #[test]
+    fn cycles_name_their_path() {
+        let bases = [base("a", &["b"]), base("b", &["a>=2"]), base("c", &[])];
+
+        let err = order(&bases).unwrap_err();
+
+        assert!(matches!(&err, RaurError::DependencyCycle(cycle) if cycle == &["a", "b", "a"]));
+        assert_eq!(err.to_string(), "dependency cycle detected: a -> b -> a");
+        assert_eq!(err.exit_code(), 13);
+    }
+}
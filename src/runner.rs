@@ -0,0 +1,270 @@
+//! Execution of external programs (pacman, makepkg, git, sudo).
+//!
+//! Everything raur runs goes through a [`CommandRunner`], so the exact
+//! command lines can be checked in tests without touching the system.
+
+use std::collections::{HashMap, VecDeque};
+use std::ffi::OsStr;
+use std::fmt;
+use std::io::{self, Read, Write};
+use std::path::PathBuf;
+use std::process::{Command, Stdio};
+use std::sync::{Arc, Mutex};
+
+/// A command line to run.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Cmd {
+    pub program: String,
+    pub args: Vec<String>,
+    pub cwd: Option<PathBuf>,
+}
+
+impl Cmd {
+    pub fn new(program: impl AsRef<OsStr>) -> Self {
+        Cmd {
+            program: program.as_ref().to_string_lossy().to_string(),
+            args: Vec::new(),
+            cwd: None,
+        }
+    }
+
+    pub fn arg(mut self, arg: impl AsRef<OsStr>) -> Self {
+        self.args.push(arg.as_ref().to_string_lossy().to_string());
+        self
+    }
+
+    pub fn args<I, S>(mut self, args: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<OsStr>,
+    {
+        self.args
+            .extend(args.into_iter().map(|arg| arg.as_ref().to_string_lossy().to_string()));
+        self
+    }
+
+    pub fn current_dir(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.cwd = Some(dir.into());
+        self
+    }
+
+    fn to_command(&self) -> Command {
+        let mut command = Command::new(&self.program);
+        command.args(&self.args);
+        if let Some(cwd) = &self.cwd {
+            command.current_dir(cwd);
+        }
+        command
+    }
+}
+
+impl fmt::Display for Cmd {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.program)?;
+        for arg in &self.args {
+            write!(f, " {}", arg)?;
+        }
+        Ok(())
+    }
+}
+
+/// Exit code and captured output of a finished command. Commands run with
+/// [`CommandRunner::status`] leave `stdout` empty; their `stderr` is shown
+/// on the terminal and kept for error reports.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CmdOutput {
+    /// `None` when the process was killed by a signal
+    pub code: Option<i32>,
+    pub stdout: String,
+    pub stderr: String,
+}
+
+impl CmdOutput {
+    /// A successful run printing `stdout`.
+    pub fn ok(stdout: impl Into<String>) -> Self {
+        CmdOutput {
+            code: Some(0),
+            stdout: stdout.into(),
+            stderr: String::new(),
+        }
+    }
+
+    /// A failed run with the given exit code.
+    pub fn failed(code: i32) -> Self {
+        CmdOutput {
+            code: Some(code),
+            ..CmdOutput::default()
+        }
+    }
+
+    pub fn success(&self) -> bool {
+        self.code == Some(0)
+    }
+}
+
+pub trait CommandRunner: Send + Sync {
+    /// Run the command and capture its output.
+    fn output(&self, cmd: &Cmd) -> io::Result<CmdOutput>;
+
+    /// Run the command attached to the terminal, capturing a copy of stderr.
+    fn status(&self, cmd: &Cmd) -> io::Result<CmdOutput>;
+
+    /// Whether commands run as root.
+    fn is_root(&self) -> bool;
+}
+
+impl<R: CommandRunner + ?Sized> CommandRunner for Arc<R> {
+    fn output(&self, cmd: &Cmd) -> io::Result<CmdOutput> {
+        (**self).output(cmd)
+    }
+
+    fn status(&self, cmd: &Cmd) -> io::Result<CmdOutput> {
+        (**self).status(cmd)
+    }
+
+    fn is_root(&self) -> bool {
+        (**self).is_root()
+    }
+}
+
+/// Runs commands on the real system.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemRunner {
+    /// Send the stdout of attached commands to stderr, so our own stdout
+    /// only carries machine-readable results
+    pub stdout_to_stderr: bool,
+}
+
+impl CommandRunner for SystemRunner {
+    fn output(&self, cmd: &Cmd) -> io::Result<CmdOutput> {
+        let output = cmd.to_command().output()?;
+        Ok(CmdOutput {
+            code: output.status.code(),
+            stdout: String::from_utf8_lossy(&output.stdout).to_string(),
+            stderr: String::from_utf8_lossy(&output.stderr).to_string(),
+        })
+    }
+
+    fn status(&self, cmd: &Cmd) -> io::Result<CmdOutput> {
+        let mut command = cmd.to_command();
+        if self.stdout_to_stderr {
+            command.stdout(io::stderr());
+        }
+        let mut child = command.stderr(Stdio::piped()).spawn()?;
+
+        // Pass stderr through as it arrives, keeping a copy
+        let mut captured = Vec::new();
+        let mut read = Ok(());
+        if let Some(mut stderr) = child.stderr.take() {
+            let mut buf = [0; 4096];
+            read = loop {
+                match stderr.read(&mut buf) {
+                    Ok(0) => break Ok(()),
+                    Ok(n) => {
+                        let _ = io::stderr().write_all(&buf[..n]);
+                        captured.extend_from_slice(&buf[..n]);
+                    }
+                    Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+                    Err(e) => break Err(e),
+                }
+            };
+        }
+
+        // Reap the child even when reading failed, so it does not linger
+        let status = child.wait()?;
+        read?;
+        Ok(CmdOutput {
+            code: status.code(),
+            stdout: String::new(),
+            stderr: String::from_utf8_lossy(&captured).to_string(),
+        })
+    }
+
+    fn is_root(&self) -> bool {
+        // SAFETY: geteuid cannot fail and has no preconditions
+        unsafe { libc::geteuid() == 0 }
+    }
+}
+
+/// Test double that records every command and replays scripted results.
+///
+/// Results are keyed by the full command line. Several results for the same
+/// command are handed out in order, the last one is repeated. Commands
+/// without a script succeed with empty output.
+#[derive(Default)]
+pub struct ScriptedRunner {
+    script: Mutex<HashMap<String, VecDeque<CmdOutput>>>,
+    calls: Mutex<Vec<Cmd>>,
+    root: bool,
+}
+
+impl ScriptedRunner {
+    pub fn new() -> Self {
+        ScriptedRunner::default()
+    }
+
+    /// Script the result of `command_line`, e.g. `"pacman -Si -- foo"`.
+    pub fn on(self, command_line: &str, output: CmdOutput) -> Self {
+        self.script
+            .lock()
+            .unwrap()
+            .entry(command_line.to_string())
+            .or_default()
+            .push_back(output);
+        self
+    }
+
+    /// Pretend to run as root.
+    pub fn as_root(mut self) -> Self {
+        self.root = true;
+        self
+    }
+
+    /// All commands run so far, as command lines.
+    pub fn calls(&self) -> Vec<String> {
+        self.calls.lock().unwrap().iter().map(Cmd::to_string).collect()
+    }
+
+    fn run(&self, cmd: &Cmd) -> CmdOutput {
+        self.calls.lock().unwrap().push(cmd.clone());
+
+        let mut script = self.script.lock().unwrap();
+        match script.get_mut(&cmd.to_string()) {
+            Some(outputs) if outputs.len() > 1 => outputs.pop_front().unwrap_or_default(),
+            Some(outputs) => outputs.front().cloned().unwrap_or_default(),
+            None => CmdOutput::ok(""),
+        }
+    }
+}
+
+impl CommandRunner for ScriptedRunner {
+    fn output(&self, cmd: &Cmd) -> io::Result<CmdOutput> {
+        Ok(self.run(cmd))
+    }
+
+    fn status(&self, cmd: &Cmd) -> io::Result<CmdOutput> {
+        let mut output = self.run(cmd);
+        output.stdout.clear();
+        Ok(output)
+    }
+
+    fn is_root(&self) -> bool {
+        self.root
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn status_keeps_exit_code_and_stderr() {
+        let output = SystemRunner::default()
+            .status(&Cmd::new("sh").args(["-c", "echo out; echo oops >&2; exit 3"]))
+            .unwrap();
+
+        assert_eq!(output.code, Some(3));
+        assert_eq!(output.stdout, "");
+        assert_eq!(output.stderr, "oops\n");
+    }
+}
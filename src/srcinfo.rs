@@ -0,0 +1,462 @@
+//! Parser for the `.SRCINFO` files of AUR clones.
+//!
+//! A `.SRCINFO` is what `makepkg --printsrcinfo` makes of a PKGBUILD: a
+//! `pkgbase` section with the shared values, followed by one `pkgname`
+//! section per package. Package sections override whole keys of the base,
+//! an empty value (`depends = `) clears them. Most list keys can be limited
+//! to one architecture with a suffix, e.g. `depends_x86_64`.
+//!
+//! Reading it lets raur reason about a clone without running bash.
+
+use std::collections::{BTreeMap, HashSet};
+use std::fmt;
+use std::path::Path;
+
+use crate::error::{RaurError, Result};
+
+/// Keys a package section may override.
+const PACKAGE_KEYS: [&str; 14] = [
+    "pkgdesc",
+    "url",
+    "arch",
+    "license",
+    "groups",
+    "depends",
+    "optdepends",
+    "provides",
+    "conflicts",
+    "replaces",
+    "backup",
+    "options",
+    "install",
+    "changelog",
+];
+
+/// Keys that only exist in the pkgbase section.
+const BASE_KEYS: [&str; 16] = [
+    "pkgver",
+    "pkgrel",
+    "epoch",
+    "makedepends",
+    "checkdepends",
+    "source",
+    "noextract",
+    "validpgpkeys",
+    "md5sums",
+    "sha1sums",
+    "sha224sums",
+    "sha256sums",
+    "sha384sums",
+    "sha512sums",
+    "b2sums",
+    "cksums",
+];
+
+/// Keys that take an `_<arch>` suffix.
+const ARCH_KEYS: [&str; 16] = [
+    "depends",
+    "optdepends",
+    "provides",
+    "conflicts",
+    "replaces",
+    "makedepends",
+    "checkdepends",
+    "source",
+    "md5sums",
+    "sha1sums",
+    "sha224sums",
+    "sha256sums",
+    "sha384sums",
+    "sha512sums",
+    "b2sums",
+    "cksums",
+];
+
+/// Keys with at most one value per section.
+const SINGLE_KEYS: [&str; 7] = ["pkgver", "pkgrel", "epoch", "pkgdesc", "url", "install", "changelog"];
+
+/// A value of an architecture specific key; `arch` is `None` for the
+/// plain key.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ArchValue {
+    pub arch: Option<String>,
+    pub value: String,
+}
+
+/// Values of `values` that apply on `arch`.
+pub fn for_arch<'a>(values: &'a [ArchValue], arch: &'a str) -> impl Iterator<Item = &'a str> {
+    values
+        .iter()
+        .filter(move |v| v.arch.as_deref().is_none_or(|a| a == arch))
+        .map(|v| v.value.as_str())
+}
+
+/// The keys a package section can override.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PackageFields {
+    pub pkgdesc: Option<String>,
+    pub url: Option<String>,
+    pub arch: Vec<String>,
+    pub license: Vec<String>,
+    pub groups: Vec<String>,
+    pub depends: Vec<ArchValue>,
+    pub optdepends: Vec<ArchValue>,
+    pub provides: Vec<ArchValue>,
+    pub conflicts: Vec<ArchValue>,
+    pub replaces: Vec<ArchValue>,
+    pub backup: Vec<String>,
+    pub options: Vec<String>,
+    pub install: Option<String>,
+    pub changelog: Option<String>,
+}
+
+/// The pkgbase section.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PackageBase {
+    pub pkgbase: String,
+    pub pkgver: String,
+    pub pkgrel: String,
+    pub epoch: Option<String>,
+    pub makedepends: Vec<ArchValue>,
+    pub checkdepends: Vec<ArchValue>,
+    pub source: Vec<ArchValue>,
+    pub noextract: Vec<String>,
+    pub validpgpkeys: Vec<String>,
+    /// Checksums by kind (`sha256sums`, `b2sums`, ...), one per source
+    pub checksums: BTreeMap<String, Vec<ArchValue>>,
+    /// Defaults for every package
+    pub fields: PackageFields,
+}
+
+/// A package section with the base values it did not override filled in.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Package {
+    pub pkgname: String,
+    pub fields: PackageFields,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Srcinfo {
+    pub base: PackageBase,
+    /// In file order, at least one
+    pub packages: Vec<Package>,
+    /// Lines that were skipped, e.g. keys of a newer makepkg
+    pub warnings: Vec<SrcinfoError>,
+}
+
+/// A parse error, or a warning, with the 1-based line it refers to.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SrcinfoError {
+    pub line: usize,
+    pub message: String,
+}
+
+impl fmt::Display for SrcinfoError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+impl std::error::Error for SrcinfoError {}
+
+fn error(line: usize, message: impl Into<String>) -> SrcinfoError {
+    SrcinfoError {
+        line,
+        message: message.into(),
+    }
+}
+
+impl Srcinfo {
+    /// Read `<clone_dir>/.SRCINFO`, or any other file.
+    pub fn from_file(path: &Path) -> Result<Srcinfo> {
+        let path = if path.is_dir() { path.join(".SRCINFO") } else { path.to_path_buf() };
+        let text = std::fs::read_to_string(&path)?;
+        Srcinfo::parse(&text).map_err(|error| RaurError::Srcinfo { path, error })
+    }
+
+    pub fn parse(text: &str) -> Result<Srcinfo, SrcinfoError> {
+        let mut parser = Parser::default();
+        for (i, line) in text.lines().enumerate() {
+            parser.line(i + 1, line)?;
+        }
+        parser.finish()
+    }
+
+    /// `[epoch:]pkgver-pkgrel`
+    pub fn version(&self) -> String {
+        match &self.base.epoch {
+            Some(epoch) => format!("{}:{}-{}", epoch, self.base.pkgver, self.base.pkgrel),
+            None => format!("{}-{}", self.base.pkgver, self.base.pkgrel),
+        }
+    }
+
+    pub fn package(&self, pkgname: &str) -> Option<&Package> {
+        self.packages.iter().find(|pkg| pkg.pkgname == pkgname)
+    }
+
+    /// Everything needed to build on `arch`: the depends of every package
+    /// plus makedepends and checkdepends, without duplicates.
+    pub fn build_dependencies<'a>(&'a self, arch: &'a str) -> Vec<&'a str> {
+        let mut seen = HashSet::new();
+        self.packages
+            .iter()
+            .flat_map(|pkg| for_arch(&pkg.fields.depends, arch))
+            .chain(for_arch(&self.base.makedepends, arch))
+            .chain(for_arch(&self.base.checkdepends, arch))
+            .filter(|dep| seen.insert(*dep))
+            .collect()
+    }
+}
+
+// ======================
+// Parser
+// ======================
+#[derive(Default)]
+struct Parser {
+    base: Option<PackageBase>,
+    base_line: usize,
+    packages: Vec<Package>,
+    /// `(key, arch)` already set in the current section
+    seen: HashSet<(String, Option<String>)>,
+    /// Line each checksum kind first appeared on
+    checksum_lines: BTreeMap<String, usize>,
+    warnings: Vec<SrcinfoError>,
+}
+
+impl Parser {
+    fn line(&mut self, n: usize, line: &str) -> Result<(), SrcinfoError> {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            return Ok(());
+        }
+
+        let Some((key, value)) = line.split_once('=') else {
+            return Err(error(n, format!("expected 'key = value', got '{}'", line)));
+        };
+        let (key, value) = (key.trim(), value.trim());
+
+        match key {
+            "pkgbase" => {
+                if self.base.is_some() {
+                    return Err(error(n, "pkgbase may only appear once"));
+                }
+                if value.is_empty() {
+                    return Err(error(n, "pkgbase is empty"));
+                }
+                self.base = Some(PackageBase {
+                    pkgbase: value.to_string(),
+                    ..PackageBase::default()
+                });
+                self.base_line = n;
+                return Ok(());
+            }
+            "pkgname" => {
+                let Some(base) = &self.base else {
+                    return Err(error(n, "pkgname before pkgbase"));
+                };
+                if value.is_empty() {
+                    return Err(error(n, "pkgname is empty"));
+                }
+                if self.packages.iter().any(|pkg| pkg.pkgname == value) {
+                    return Err(error(n, format!("package '{}' appears twice", value)));
+                }
+                self.packages.push(Package {
+                    pkgname: value.to_string(),
+                    fields: base.fields.clone(),
+                });
+                self.seen.clear();
+                return Ok(());
+            }
+            _ => {}
+        }
+
+        if self.base.is_none() {
+            return Err(error(n, format!("'{}' before pkgbase", key)));
+        }
+
+        let (name, arch) = split_arch(key);
+        if !PACKAGE_KEYS.contains(&name) && !BASE_KEYS.contains(&name) {
+            self.warnings.push(error(n, format!("unknown key '{}' skipped", key)));
+            return Ok(());
+        }
+        if arch == Some("") {
+            return Err(error(n, format!("'{}' has an empty architecture suffix", key)));
+        }
+        if arch.is_some() && !ARCH_KEYS.contains(&name) {
+            return Err(error(n, format!("'{}' cannot be architecture specific", name)));
+        }
+
+        let first = self.seen.insert((name.to_string(), arch.map(str::to_string)));
+        if !first && SINGLE_KEYS.contains(&name) {
+            return Err(error(n, format!("'{}' may only appear once per section", name)));
+        }
+        let value = ArchValue {
+            arch: arch.map(str::to_string),
+            value: value.to_string(),
+        };
+
+        match self.packages.last_mut() {
+            Some(pkg) => {
+                if !PACKAGE_KEYS.contains(&name) {
+                    return Err(error(n, format!("'{}' belongs in the pkgbase section", name)));
+                }
+                // The first occurrence replaces what the package inherited
+                set_field(&mut pkg.fields, name, value, first);
+            }
+            None => {
+                let base = self.base.as_mut().expect("checked above");
+                if PACKAGE_KEYS.contains(&name) {
+                    set_field(&mut base.fields, name, value, false);
+                } else {
+                    set_base(base, name, value, n)?;
+                    if name.ends_with("sums") {
+                        self.checksum_lines.entry(name.to_string()).or_insert(n);
+                    }
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    fn finish(self) -> Result<Srcinfo, SrcinfoError> {
+        let Some(base) = self.base else {
+            return Err(error(1, "no pkgbase"));
+        };
+        if base.pkgver.is_empty() {
+            return Err(error(self.base_line, format!("pkgbase '{}' has no pkgver", base.pkgbase)));
+        }
+        if base.pkgrel.is_empty() {
+            return Err(error(self.base_line, format!("pkgbase '{}' has no pkgrel", base.pkgbase)));
+        }
+        if self.packages.is_empty() {
+            return Err(error(self.base_line, format!("pkgbase '{}' has no pkgname", base.pkgbase)));
+        }
+
+        // Every checksum array must match its sources one to one
+        for (kind, sums) in &base.checksums {
+            let mut arches: Vec<Option<&str>> = sums.iter().chain(&base.source).map(|v| v.arch.as_deref()).collect();
+            arches.sort();
+            arches.dedup();
+            for arch in arches {
+                let count = |values: &[ArchValue]| values.iter().filter(|v| v.arch.as_deref() == arch).count();
+                let (sources, checksums) = (count(&base.source), count(sums));
+                if sources != checksums {
+                    let source_key = arch.map_or("source".to_string(), |a| format!("source_{}", a));
+                    return Err(error(
+                        self.checksum_lines[kind],
+                        format!("{} has {} checksum(s) for {} {}", kind, checksums, sources, source_key),
+                    ));
+                }
+            }
+        }
+
+        Ok(Srcinfo {
+            base,
+            packages: self.packages,
+            warnings: self.warnings,
+        })
+    }
+}
+
+/// `depends_x86_64` -> `("depends", Some("x86_64"))`. Only splits off the
+/// suffix of keys that can have one, so `b2sums` stays whole.
+fn split_arch(key: &str) -> (&str, Option<&str>) {
+    for name in ARCH_KEYS {
+        if let Some(arch) = key.strip_prefix(name).and_then(|rest| rest.strip_prefix('_')) {
+            if !arch.is_empty() {
+                return (name, Some(arch));
+            }
+        }
+    }
+    // Not an architecture key; report the suffix on whatever it was glued to
+    match key.split_once('_') {
+        Some((name, arch)) if PACKAGE_KEYS.contains(&name) || BASE_KEYS.contains(&name) => (name, Some(arch)),
+        _ => (key, None),
+    }
+}
+
+fn set_field(fields: &mut PackageFields, name: &str, value: ArchValue, replace: bool) {
+    let list = |list: &mut Vec<String>, value: ArchValue| {
+        if replace {
+            list.clear();
+        }
+        if !value.value.is_empty() {
+            list.push(value.value);
+        }
+    };
+    let arch_list = |list: &mut Vec<ArchValue>, value: ArchValue| {
+        if replace {
+            list.retain(|v| v.arch != value.arch);
+        }
+        if !value.value.is_empty() {
+            list.push(value);
+        }
+    };
+    let single = |field: &mut Option<String>, value: ArchValue| {
+        *field = Some(value.value).filter(|v| !v.is_empty());
+    };
+
+    match name {
+        "pkgdesc" => single(&mut fields.pkgdesc, value),
+        "url" => single(&mut fields.url, value),
+        "install" => single(&mut fields.install, value),
+        "changelog" => single(&mut fields.changelog, value),
+        "arch" => list(&mut fields.arch, value),
+        "license" => list(&mut fields.license, value),
+        "groups" => list(&mut fields.groups, value),
+        "backup" => list(&mut fields.backup, value),
+        "options" => list(&mut fields.options, value),
+        "depends" => arch_list(&mut fields.depends, value),
+        "optdepends" => arch_list(&mut fields.optdepends, value),
+        "provides" => arch_list(&mut fields.provides, value),
+        "conflicts" => arch_list(&mut fields.conflicts, value),
+        "replaces" => arch_list(&mut fields.replaces, value),
+        _ => unreachable!("not a package key: {}", name),
+    }
+}
+
+fn set_base(base: &mut PackageBase, name: &str, value: ArchValue, n: usize) -> Result<(), SrcinfoError> {
+    let push = |list: &mut Vec<ArchValue>, value: ArchValue| {
+        if !value.value.is_empty() {
+            list.push(value);
+        }
+    };
+
+    match name {
+        "pkgver" => {
+            if value.value.is_empty() || value.value.contains(['-', ':', '/']) || value.value.contains(char::is_whitespace) {
+                return Err(error(n, format!("invalid pkgver '{}'", value.value)));
+            }
+            base.pkgver = value.value;
+        }
+        "pkgrel" => {
+            let valid = value.value.split('.').all(|part| !part.is_empty() && part.bytes().all(|b| b.is_ascii_digit()));
+            if !valid {
+                return Err(error(n, format!("invalid pkgrel '{}'", value.value)));
+            }
+            base.pkgrel = value.value;
+        }
+        "epoch" => {
+            if value.value.parse::<u32>().is_err() {
+                return Err(error(n, format!("invalid epoch '{}'", value.value)));
+            }
+            base.epoch = Some(value.value).filter(|epoch| epoch != "0");
+        }
+        "makedepends" => push(&mut base.makedepends, value),
+        "checkdepends" => push(&mut base.checkdepends, value),
+        "source" => push(&mut base.source, value),
+        "noextract" if !value.value.is_empty() => base.noextract.push(value.value),
+        "noextract" => {}
+        "validpgpkeys" => {
+            let key = &value.value;
+            if key.len() != 40 || !key.bytes().all(|b| b.is_ascii_hexdigit()) {
+                return Err(error(n, format!("validpgpkeys needs full 40 character fingerprints, got '{}'", key)));
+            }
+            base.validpgpkeys.push(value.value);
+        }
+        sums => push(base.checksums.entry(sums.to_string()).or_default(), value),
+    }
+
+    Ok(())
+}
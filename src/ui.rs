@@ -0,0 +1,653 @@
+//! Terminal interaction: prompts, the numbered package menu, result tables
+//! and the build review.
+//!
+//! Questions go through the context's [`Prompter`], so callers without a
+//! terminal can answer them from a script. Prompts and the review are
+//! written to stderr; stdout is kept for results.
+
+use std::collections::{BTreeSet, VecDeque};
+use std::io::{stderr, stdin, BufRead, IsTerminal, Write};
+use std::path::Path;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use colored::*;
+use indicatif::{MultiProgress, ProgressBar, ProgressStyle};
+use prettytable::{Cell, Row, Table};
+use serde::Serialize;
+
+use crate::aur::{AurClient, AurPackage};
+use crate::build::{self, AurInstall, CleanPolicy, CleanReport, ReviewState};
+use crate::config::{ProviderPolicy, RemovePolicy, ReviewPolicy, Source};
+use crate::error::{CommandFailure, Missing, RaurError, Result};
+use crate::pacman::{RepoInfo, RepoPackage};
+use crate::resolve::{PackageInfo, RepoTarget, UpgradeCheck};
+use crate::Context;
+
+// ======================
+// Output formats
+// ======================
+/// How command results are printed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, clap::ValueEnum)]
+pub enum Format {
+    /// Colored tables and messages
+    #[default]
+    Human,
+    /// One versioned JSON document per command
+    Json,
+    /// Tab-separated lines without colors
+    Plain,
+}
+
+/// Version of the JSON documents. Fields may be added within a version;
+/// renaming or removing one bumps it.
+pub const SCHEMA: &str = "raur/v1";
+
+/// A result as printed with `--format json`: `{"schema": "raur/v1", "kind": ..., ...}`.
+#[derive(Debug, Serialize)]
+pub struct Document<'a, T: Serialize> {
+    pub schema: &'static str,
+    pub kind: &'a str,
+    #[serde(flatten)]
+    pub data: &'a T,
+}
+
+impl<'a, T: Serialize> Document<'a, T> {
+    pub fn new(kind: &'a str, data: &'a T) -> Self {
+        Document { schema: SCHEMA, kind, data }
+    }
+
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).expect("documents always serialize")
+    }
+}
+
+/// `kind: "search"`. A source is `null` when it was not searched.
+#[derive(Debug, Serialize)]
+pub struct SearchReport {
+    pub query: String,
+    pub by: String,
+    pub repo: Option<Vec<RepoPackage>>,
+    /// AUR records with their RPC field names
+    pub aur: Option<Vec<AurPackage>>,
+}
+
+/// `kind: "info"`
+#[derive(Debug, Serialize)]
+pub struct InfoReport {
+    pub packages: Vec<PackageInfo>,
+}
+
+/// `kind: "install"`
+#[derive(Debug, Default, Serialize)]
+pub struct InstallReport {
+    pub repo: Vec<RepoTarget>,
+    pub aur: Option<AurInstall>,
+}
+
+/// `kind: "upgrade"`; `aur.outdated` is the upgrade plan, `installed` what
+/// was built from it.
+#[derive(Debug, Serialize)]
+pub struct UpgradeReport {
+    pub aur: UpgradeCheck,
+    pub installed: Option<AurInstall>,
+}
+
+/// `kind: "remove"`
+#[derive(Debug, Serialize)]
+pub struct RemoveReport {
+    pub removed: Vec<String>,
+    pub purge: bool,
+}
+
+/// `kind: "update"`
+#[derive(Debug, Serialize)]
+pub struct UpdateReport {
+    pub full: bool,
+}
+
+/// `kind: "clean"`
+#[derive(Debug, Serialize)]
+pub struct CleanSummary {
+    pub policy: CleanPolicy,
+    #[serde(flatten)]
+    pub report: CleanReport,
+}
+
+/// One setting with the layer its value came from.
+#[derive(Debug, Serialize)]
+pub struct Setting {
+    pub key: String,
+    pub value: String,
+    pub source: Source,
+}
+
+/// `kind: "config"`, from `config get` and `config list`
+#[derive(Debug, Serialize)]
+pub struct ConfigReport {
+    pub settings: Vec<Setting>,
+}
+
+/// `kind: "config-set"`
+#[derive(Debug, Serialize)]
+pub struct ConfigUpdate {
+    pub key: String,
+    pub value: String,
+    pub file: String,
+}
+
+/// `kind: "error"`, printed on stdout instead of a result.
+#[derive(Debug, Serialize)]
+pub struct ErrorReport<'a> {
+    pub code: i32,
+    pub message: String,
+    pub failure: Option<&'a CommandFailure>,
+    pub missing: &'a [Missing],
+}
+
+impl<'a> ErrorReport<'a> {
+    pub fn new(error: &'a RaurError) -> Self {
+        ErrorReport {
+            code: error.exit_code(),
+            message: error.to_string(),
+            failure: error.command_failure(),
+            missing: match error {
+                RaurError::NotFound(missing) => missing,
+                _ => &[],
+            },
+        }
+    }
+}
+
+// ======================
+// Prompts
+// ======================
+pub trait Prompter: Send + Sync {
+    /// Show `question` and return the answer line.
+    fn prompt(&self, question: &str) -> std::io::Result<String>;
+
+    /// Whether anyone can answer. Questions fail fast when nobody can.
+    fn is_interactive(&self) -> bool {
+        true
+    }
+}
+
+/// Reads answers from stdin.
+pub struct TerminalPrompter;
+
+impl Prompter for TerminalPrompter {
+    fn prompt(&self, question: &str) -> std::io::Result<String> {
+        eprint!("{}", question);
+        stderr().flush()?;
+
+        let mut input = String::new();
+        stdin().lock().read_line(&mut input)?;
+        Ok(input)
+    }
+
+    fn is_interactive(&self) -> bool {
+        stdin().is_terminal()
+    }
+}
+
+/// Answers questions from a fixed list, in order. Once the list runs out
+/// every question gets an empty answer, i.e. the default.
+#[derive(Default)]
+pub struct ScriptedPrompter {
+    answers: Mutex<VecDeque<String>>,
+}
+
+impl ScriptedPrompter {
+    pub fn new<I, S>(answers: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        ScriptedPrompter {
+            answers: Mutex::new(answers.into_iter().map(Into::into).collect()),
+        }
+    }
+}
+
+impl Prompter for ScriptedPrompter {
+    fn prompt(&self, question: &str) -> std::io::Result<String> {
+        let answer = self.answers.lock().unwrap().pop_front().unwrap_or_default();
+        eprintln!("{}{}", question, answer);
+        Ok(answer)
+    }
+}
+
+/// Ask a yes/no question that defaults to no.
+pub fn confirm(ctx: &Context, question: &str) -> Result<bool> {
+    let input = ctx.prompt(&format!("{} [y/N]: ", question))?;
+    Ok(matches!(input.trim().to_lowercase().as_str(), "y" | "yes"))
+}
+
+/// Whether `pkgname` may be removed, following the `remove` policy.
+pub fn confirm_removal(ctx: &Context, pkgname: &str) -> Result<bool> {
+    match ctx.config().remove {
+        RemovePolicy::Ask => confirm(ctx, &format!("⚠️  Are you sure you want to remove '{}'?", pkgname)),
+        RemovePolicy::Yes => Ok(true),
+        RemovePolicy::No => Ok(false),
+    }
+}
+
+/// Pick one of several providers, asking unless the `provider` policy
+/// says otherwise.
+pub fn choose_provider(ctx: &Context, name: &str, providers: &[String]) -> Result<String> {
+    if ctx.config().provider == ProviderPolicy::First {
+        eprintln!(":: Using {} for {}", providers[0].bold(), name.bold());
+        return Ok(providers[0].clone());
+    }
+
+    eprintln!(":: There are {} providers available for {}:", providers.len(), name.bold());
+    for (i, provider) in providers.iter().enumerate() {
+        eprintln!("  {}) {}", i + 1, provider);
+    }
+
+    loop {
+        let input = ctx.prompt("Enter a number (default=1): ")?;
+        let input = input.trim();
+        if input.is_empty() {
+            return Ok(providers[0].clone());
+        }
+        match input.parse::<usize>() {
+            Ok(n) if (1..=providers.len()).contains(&n) => return Ok(providers[n - 1].clone()),
+            _ => eprintln!("Please enter a number between 1 and {}", providers.len()),
+        }
+    }
+}
+
+// ======================
+// Build review
+// ======================
+/// Show what is about to be built and let the user accept, edit or abort.
+/// The `review` policy may skip showing or asking.
+pub fn review(ctx: &Context, pkgname: &str, clone_dir: &Path, head: &str) -> Result<bool> {
+    let policy = ctx.config().review;
+    if policy == ReviewPolicy::Skip {
+        eprintln!("⏭️  Skipping the review of '{}'", pkgname.yellow());
+        return Ok(true);
+    }
+
+    match build::review_state(ctx, pkgname, clone_dir, head)? {
+        ReviewState::Unchanged => {
+            eprintln!("✅ '{}' is unchanged since the last review", pkgname.green());
+            return Ok(true);
+        }
+        ReviewState::Changed { since } => {
+            eprintln!("📝 Changes to '{}' since the last review:", pkgname.yellow());
+            ctx.runner()
+                .status(&build::git(clone_dir).args(["--no-pager", "diff", "--color=always", &since, "HEAD"]))?;
+        }
+        ReviewState::New => {
+            eprintln!("📝 Build files of '{}':", pkgname.yellow());
+            for file in build::build_files(clone_dir)? {
+                eprintln!("{}", format!("==> {}", file.display()).bold());
+                eprintln!("{}", std::fs::read_to_string(clone_dir.join(&file))?);
+            }
+        }
+    }
+
+    if policy == ReviewPolicy::Show {
+        return Ok(true);
+    }
+
+    loop {
+        let input = ctx.prompt(&format!("==> Build '{}'? [Y]es/[e]dit/[a]bort: ", pkgname))?;
+
+        match input.trim().to_lowercase().as_str() {
+            "" | "y" | "yes" => return Ok(true),
+            "a" | "abort" | "n" | "no" => return Ok(false),
+            "e" | "edit" => {
+                if !build::edit_build_files(ctx, clone_dir)? {
+                    eprintln!("⚠️ Editor exited with an error");
+                }
+            }
+            _ => eprintln!("Please answer y, e or a"),
+        }
+    }
+}
+
+pub fn spinner(message: &str) -> ProgressBar {
+    let pb = ProgressBar::new_spinner();
+    pb.set_style(
+        ProgressStyle::default_spinner()
+            .template("{spinner} {msg}")
+            .unwrap()
+            .tick_strings(&["⠁","⠂","⠄","⡀","⢀","⠠","⠐","⠈"])
+    );
+    pb.enable_steady_tick(Duration::from_millis(100));
+    pb.set_message(message.to_string());
+    pb
+}
+
+/// A line of the parallel fetch display: `<spinner> <pkgname> <status>`.
+pub fn fetch_bar(progress: &MultiProgress, pkgname: &str) -> ProgressBar {
+    let pb = progress.add(ProgressBar::new_spinner());
+    pb.set_style(
+        ProgressStyle::default_spinner()
+            .template("{spinner} {prefix:.bold} {msg}")
+            .unwrap()
+            .tick_strings(&["⠁","⠂","⠄","⡀","⢀","⠠","⠐","⠈","✔"])
+    );
+    pb.set_prefix(pkgname.to_string());
+    pb.set_message("waiting");
+    pb
+}
+
+// ======================
+// Search results
+// ======================
+/// `limit` of 0 prints all results.
+pub fn print_repo_results(results: &[RepoPackage], limit: usize) {
+    for pkg in results.iter().take(display_limit(limit)) {
+        let installed = if pkg.installed { " [installed]" } else { "" };
+        println!("  {}", format!("{}/{} {}{}", pkg.repo, pkg.name, pkg.version, installed).green());
+        println!("      {}", pkg.description);
+    }
+}
+
+pub fn print_aur_results(results: &[AurPackage], limit: usize) {
+    let mut table = Table::new();
+    table.add_row(Row::new(vec![
+        Cell::new("Name"),
+        Cell::new("Version"),
+        Cell::new("Description"),
+    ]));
+
+    for pkg in results.iter().take(display_limit(limit)) {
+        table.add_row(Row::new(vec![
+            Cell::new(&pkg.name.green().to_string()),
+            Cell::new(&pkg.version.yellow().to_string()),
+            Cell::new(&pkg.description.clone().unwrap_or_else(|| "No description".into())),
+        ]));
+    }
+
+    table.printstd();
+}
+
+fn display_limit(limit: usize) -> usize {
+    if limit == 0 { usize::MAX } else { limit }
+}
+
+// ======================
+// Package info
+// ======================
+/// Print a package like `pacman -Si` does, followed by its install state.
+pub fn print_package_info(aur: &AurClient, info: &PackageInfo) {
+    let installed = match info.installed_version() {
+        Some(version) => format!("Yes ({})", version).green().to_string(),
+        None => "No".to_string(),
+    };
+
+    if let Some(pkg) = &info.repo {
+        print_repo_info(pkg);
+    } else if let Some(pkg) = &info.aur {
+        print_aur_info(aur, pkg);
+    } else if let Some(pkg) = &info.local {
+        print_repo_info(pkg);
+    }
+
+    if let Some(local) = &info.local {
+        print_field("Install Date", local.install_date.map_or("None".to_string(), format_timestamp));
+        print_field("Install Reason", local.install_reason.as_deref().unwrap_or("None"));
+    }
+    print_field("Installed", &installed);
+    println!();
+}
+
+fn print_repo_info(pkg: &RepoInfo) {
+    print_field("Repository", pkg.repository.as_deref().unwrap_or("local").blue());
+    print_field("Name", pkg.name.bold());
+    print_field("Version", pkg.version.green());
+    print_field("Description", &pkg.description);
+    print_field("URL", &pkg.url);
+    print_list("Licenses", &pkg.licenses);
+    print_list("Groups", &pkg.groups);
+    print_list("Provides", &pkg.provides);
+    print_list("Depends On", &pkg.depends);
+    print_lines("Optional Deps", &pkg.optional_deps);
+    print_list("Conflicts With", &pkg.conflicts);
+    print_list("Replaces", &pkg.replaces);
+    if let Some(size) = &pkg.download_size {
+        print_field("Download Size", size);
+    }
+    print_field("Installed Size", &pkg.installed_size);
+    print_field("Packager", &pkg.packager);
+    print_field("Build Date", pkg.build_date.map(format_timestamp).unwrap_or_default());
+}
+
+fn print_aur_info(aur: &AurClient, pkg: &AurPackage) {
+    let out_of_date = match pkg.out_of_date {
+        Some(since) => format!("Yes (since {})", format_timestamp(since)).red().to_string(),
+        None => "No".to_string(),
+    };
+
+    print_field("Repository", "aur".blue());
+    print_field("Name", pkg.name.bold());
+    print_field("Package Base", &pkg.package_base);
+    print_field("Version", pkg.version.green());
+    print_field("Description", pkg.description.as_deref().unwrap_or("None"));
+    print_field("URL", pkg.url.as_deref().unwrap_or("None"));
+    print_field("AUR URL", aur.package_url(&pkg.name));
+    print_list("Licenses", &pkg.license);
+    print_list("Groups", &pkg.groups);
+    print_list("Provides", &pkg.provides);
+    print_list("Depends On", &pkg.depends);
+    print_list("Make Deps", &pkg.make_depends);
+    print_list("Check Deps", &pkg.check_depends);
+    print_lines("Optional Deps", &pkg.opt_depends);
+    print_list("Conflicts With", &pkg.conflicts);
+    print_list("Replaces", &pkg.replaces);
+    print_list("Keywords", &pkg.keywords);
+    print_field("Maintainer", pkg.maintainer.as_deref().unwrap_or("None (orphaned)"));
+    print_field("Votes", pkg.num_votes);
+    print_field("Popularity", format!("{:.2}", pkg.popularity));
+    print_field("First Submitted", format_timestamp(pkg.first_submitted));
+    print_field("Last Modified", format_timestamp(pkg.last_modified));
+    print_field("Out Of Date", out_of_date);
+}
+
+fn print_field(key: &str, value: impl std::fmt::Display) {
+    println!("{:<16}: {}", key.bold(), value);
+}
+
+fn print_list(key: &str, values: &[String]) {
+    if values.is_empty() {
+        print_field(key, "None");
+    } else {
+        print_field(key, values.join("  "));
+    }
+}
+
+/// One value per line, like pacman prints optional dependencies.
+fn print_lines(key: &str, values: &[String]) {
+    let indent = format!("\n{:<18}", "");
+    if values.is_empty() {
+        print_field(key, "None");
+    } else {
+        print_field(key, values.join(&indent));
+    }
+}
+
+/// Format a Unix timestamp as a UTC date, e.g. `2024-03-01 12:00 UTC`.
+pub fn format_timestamp(timestamp: i64) -> String {
+    let days = timestamp.div_euclid(86400);
+    let seconds = timestamp.rem_euclid(86400);
+
+    // Civil date from days since 1970-01-01 (Howard Hinnant's algorithm)
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z.rem_euclid(146097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+
+    format!("{:04}-{:02}-{:02} {:02}:{:02} UTC", year, month, day, seconds / 3600, seconds % 3600 / 60)
+}
+
+// ======================
+// Numbered menu
+// ======================
+/// Print `entries` as a numbered list and ask which of them to install.
+/// Returns the chosen packages as `repo/name`, with `aur` for AUR entries,
+/// so each one is installed from where it was listed.
+pub fn select_packages(ctx: &Context, entries: &[RepoPackage]) -> Result<Vec<String>> {
+    for (i, pkg) in entries.iter().enumerate() {
+        let installed = if pkg.installed { " [installed]".cyan().to_string() } else { String::new() };
+        println!(
+            "{} {}/{} {}{}",
+            (i + 1).to_string().magenta(),
+            pkg.repo.blue(),
+            pkg.name.bold(),
+            pkg.version.green(),
+            installed
+        );
+        println!("    {}", pkg.description);
+    }
+
+    let input = ctx.prompt("==> Packages to install (eg: 1 2 3, 1-3 or ^4): ")?;
+    let selection = parse_selection(&input, entries.len())
+        .map_err(|e| RaurError::Other(format!("invalid selection: {}", e)))?;
+
+    Ok(selection
+        .into_iter()
+        .map(|index| format!("{}/{}", entries[index - 1].repo, entries[index - 1].name))
+        .collect())
+}
+
+/// Parse a selection such as `1 3 5-7 ^4` into sorted, 1-based indices.
+///
+/// Numbers and ranges add entries, `^` excludes them again. A selection made
+/// only of exclusions starts from every entry, so `^2` means "all but 2".
+pub fn parse_selection(input: &str, count: usize) -> Result<Vec<usize>, String> {
+    let mut include = BTreeSet::new();
+    let mut exclude = BTreeSet::new();
+    let mut any_include = false;
+    let mut any_exclude = false;
+
+    for token in input.split(|c: char| c.is_whitespace() || c == ',') {
+        if token.is_empty() {
+            continue;
+        }
+
+        let (target, spec) = match token.strip_prefix('^') {
+            Some(spec) => {
+                any_exclude = true;
+                (&mut exclude, spec)
+            }
+            None => {
+                any_include = true;
+                (&mut include, token)
+            }
+        };
+
+        let (start, end) = match spec.split_once('-') {
+            Some((start, end)) => (parse_index(start, count)?, parse_index(end, count)?),
+            None => {
+                let index = parse_index(spec, count)?;
+                (index, index)
+            }
+        };
+        if start > end {
+            return Err(format!("invalid range '{}'", token));
+        }
+        target.extend(start..=end);
+    }
+
+    if any_exclude && !any_include {
+        include.extend(1..=count);
+    }
+
+    Ok(include.difference(&exclude).copied().collect())
+}
+
+fn parse_index(value: &str, count: usize) -> Result<usize, String> {
+    match value.parse::<usize>() {
+        Ok(index) if (1..=count).contains(&index) => Ok(index),
+        Ok(index) => Err(format!("{} is out of range (1-{})", index, count)),
+        Err(_) => Err(format!("'{}' is not a number", value)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn numbers_ranges_and_exclusions() {
+        assert_eq!(parse_selection("1 3 5-7 ^6", 10), Ok(vec![1, 3, 5, 7]));
+        assert_eq!(parse_selection("2,1 1", 3), Ok(vec![1, 2]));
+        assert_eq!(parse_selection("1-4 ^2-3", 4), Ok(vec![1, 4]));
+    }
+
+    #[test]
+    fn only_exclusions_select_the_rest() {
+        assert_eq!(parse_selection("^4", 5), Ok(vec![1, 2, 3, 5]));
+        assert_eq!(parse_selection("", 2), Ok(vec![]));
+    }
+
+    #[test]
+    fn confirm_defaults_to_no() {
+        let ctx = Context::new().with_prompter(ScriptedPrompter::new(["y", "YES", "n", ""]));
+
+        let answers: Vec<bool> = (0..4).map(|_| confirm(&ctx, "Remove?").unwrap()).collect();
+        assert_eq!(answers, [true, true, false, false]);
+    }
+
+    #[test]
+    fn documents_carry_schema_and_kind() {
+        let report = SearchReport {
+            query: "foo".into(),
+            by: "name-desc".into(),
+            repo: None,
+            aur: Some(vec![AurPackage {
+                name: "foo-git".into(),
+                version: "1-1".into(),
+                num_votes: 3,
+                ..AurPackage::default()
+            }]),
+        };
+
+        let doc: serde_json::Value = serde_json::from_str(&Document::new("search", &report).to_json()).unwrap();
+        assert_eq!(doc["schema"], "raur/v1");
+        assert_eq!(doc["kind"], "search");
+        assert_eq!(doc["repo"], serde_json::Value::Null);
+        assert_eq!(doc["aur"][0]["Name"], "foo-git");
+        assert_eq!(doc["aur"][0]["NumVotes"], 3);
+    }
+
+    #[test]
+    fn error_documents_list_missing_packages() {
+        let error = RaurError::NotFound(vec![Missing {
+            name: "bar".into(),
+            required_by: Some("foo".into()),
+        }]);
+
+        let doc = serde_json::to_value(Document::new("error", &ErrorReport::new(&error))).unwrap();
+        assert_eq!(doc["code"], 5);
+        assert_eq!(doc["message"], "could not find 'bar' (required by 'foo')");
+        assert_eq!(doc["missing"][0]["required_by"], "foo");
+        assert_eq!(doc["failure"], serde_json::Value::Null);
+    }
+
+    #[test]
+    fn timestamps_are_utc_dates() {
+        assert_eq!(format_timestamp(0), "1970-01-01 00:00 UTC");
+        assert_eq!(format_timestamp(951782400), "2000-02-29 00:00 UTC");
+        assert_eq!(format_timestamp(1700000000), "2023-11-14 22:13 UTC");
+        assert_eq!(format_timestamp(1709251199), "2024-02-29 23:59 UTC");
+    }
+
+    #[test]
+    fn invalid_selections() {
+        assert!(parse_selection("0", 3).is_err());
+        assert!(parse_selection("4", 3).is_err());
+        assert!(parse_selection("3-1", 3).is_err());
+        assert!(parse_selection("abc", 3).is_err());
+    }
+}
@@ -0,0 +1,266 @@
+//! Package version comparison compatible with pacman's `vercmp`.
+//!
+//! Versions have the form `[epoch:]pkgver[-pkgrel]` and are compared the way
+//! libalpm's `alpm_pkg_vercmp` does: epoch first, then pkgver, then pkgrel
+//! (only when both sides have one), each with the `rpmvercmp` segment rules.
+
+use std::cmp::Ordering;
+use std::fmt;
+
+/// A parsed `[epoch:]pkgver[-pkgrel]` version string.
+#[derive(Debug, Clone)]
+pub struct Version {
+    raw: String,
+    epoch: String,
+    pkgver: String,
+    pkgrel: Option<String>,
+}
+
+impl Version {
+    pub fn new(version: &str) -> Self {
+        // The epoch is a run of leading digits terminated by ':'
+        let digits = version.bytes().take_while(u8::is_ascii_digit).count();
+        let (epoch, rest) = match version[digits..].strip_prefix(':') {
+            Some(rest) if digits > 0 => (&version[..digits], rest),
+            Some(rest) => ("0", rest),
+            None => ("0", version),
+        };
+
+        let (pkgver, pkgrel) = match rest.rsplit_once('-') {
+            Some((pkgver, pkgrel)) => (pkgver, Some(pkgrel.to_string())),
+            None => (rest, None),
+        };
+
+        Version {
+            raw: version.to_string(),
+            epoch: epoch.to_string(),
+            pkgver: pkgver.to_string(),
+            pkgrel,
+        }
+    }
+}
+
+impl From<&str> for Version {
+    fn from(version: &str) -> Self {
+        Version::new(version)
+    }
+}
+
+impl fmt::Display for Version {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.raw)
+    }
+}
+
+impl Ord for Version {
+    fn cmp(&self, other: &Self) -> Ordering {
+        if self.raw == other.raw {
+            return Ordering::Equal;
+        }
+
+        rpmvercmp(&self.epoch, &other.epoch)
+            .then_with(|| rpmvercmp(&self.pkgver, &other.pkgver))
+            .then_with(|| match (&self.pkgrel, &other.pkgrel) {
+                // A missing pkgrel matches any pkgrel, like in libalpm
+                (Some(a), Some(b)) => rpmvercmp(a, b),
+                _ => Ordering::Equal,
+            })
+    }
+}
+
+impl PartialOrd for Version {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl PartialEq for Version {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Version {}
+
+/// Compare two full version strings, equivalent to `vercmp a b`.
+pub fn vercmp(a: &str, b: &str) -> Ordering {
+    Version::new(a).cmp(&Version::new(b))
+}
+
+/// Compare a single version component segment by segment.
+fn rpmvercmp(a: &str, b: &str) -> Ordering {
+    if a == b {
+        return Ordering::Equal;
+    }
+
+    let (a, b) = (a.as_bytes(), b.as_bytes());
+    let (mut one, mut two) = (0, 0);
+
+    while one < a.len() && two < b.len() {
+        let (seg_one, seg_two) = (one, two);
+        while one < a.len() && !a[one].is_ascii_alphanumeric() {
+            one += 1;
+        }
+        while two < b.len() && !b[two].is_ascii_alphanumeric() {
+            two += 1;
+        }
+
+        if one == a.len() || two == b.len() {
+            break;
+        }
+
+        // Runs of separators of different length decide on their own
+        if one - seg_one != two - seg_two {
+            return (one - seg_one).cmp(&(two - seg_two));
+        }
+
+        let is_num = a[one].is_ascii_digit();
+        let matches: fn(&u8) -> bool = if is_num {
+            u8::is_ascii_digit
+        } else {
+            u8::is_ascii_alphabetic
+        };
+        let end_one = one + a[one..].iter().take_while(|c| matches(c)).count();
+        let end_two = two + b[two..].iter().take_while(|c| matches(c)).count();
+
+        // A numeric segment is newer than an alpha one
+        if end_two == two {
+            return if is_num { Ordering::Greater } else { Ordering::Less };
+        }
+
+        let mut seg_a = &a[one..end_one];
+        let mut seg_b = &b[two..end_two];
+        if is_num {
+            seg_a = trim_leading_zeros(seg_a);
+            seg_b = trim_leading_zeros(seg_b);
+            match seg_a.len().cmp(&seg_b.len()) {
+                Ordering::Equal => {}
+                ord => return ord,
+            }
+        }
+        match seg_a.cmp(seg_b) {
+            Ordering::Equal => {}
+            ord => return ord,
+        }
+
+        one = end_one;
+        two = end_two;
+    }
+
+    let rest_one = &a[one.min(a.len())..];
+    let rest_two = &b[two.min(b.len())..];
+    if rest_one.is_empty() && rest_two.is_empty() {
+        return Ordering::Equal;
+    }
+
+    // A remaining alpha segment never beats an empty one
+    let one_alpha = rest_one.first().is_some_and(u8::is_ascii_alphabetic);
+    let two_alpha = rest_two.first().is_some_and(u8::is_ascii_alphabetic);
+    if (rest_one.is_empty() && !two_alpha) || one_alpha {
+        Ordering::Less
+    } else {
+        Ordering::Greater
+    }
+}
+
+fn trim_leading_zeros(digits: &[u8]) -> &[u8] {
+    let zeros = digits.iter().take_while(|c| **c == b'0').count();
+    &digits[zeros..]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Taken from pacman's test/util/vercmptest.sh
+    const CASES: &[(&str, &str, i32)] = &[
+        // all similar length, no pkgrel
+        ("1.5.0", "1.5.0", 0),
+        ("1.5.1", "1.5.0", 1),
+        // mixed length
+        ("1.5.1", "1.5", 1),
+        // with pkgrel, simple
+        ("1.5.0-1", "1.5.0-1", 0),
+        ("1.5.0-1", "1.5.0-2", -1),
+        ("1.5.0-1", "1.5.1-1", -1),
+        ("1.5.0-2", "1.5.1-1", -1),
+        // with pkgrel, mixed lengths
+        ("1.5-1", "1.5.1-1", -1),
+        ("1.5-2", "1.5.1-1", -1),
+        ("1.5-2", "1.5.1-2", -1),
+        // mixed pkgrel inclusion
+        ("1.5", "1.5-1", 0),
+        ("1.5-1", "1.5", 0),
+        ("1.1-1", "1.1", 0),
+        ("1.0-1", "1.1", -1),
+        ("1.1-1", "1.0", 1),
+        // alphanumeric versions
+        ("1.5b-1", "1.5-1", -1),
+        ("1.5b", "1.5", -1),
+        ("1.5b-1", "1.5", -1),
+        ("1.5b", "1.5.1", -1),
+        // from the manpage
+        ("1.0a", "1.0alpha", -1),
+        ("1.0alpha", "1.0b", -1),
+        ("1.0b", "1.0beta", -1),
+        ("1.0beta", "1.0rc", -1),
+        ("1.0rc", "1.0", -1),
+        // going crazy? alpha-dotted versions
+        ("1.5.a", "1.5", 1),
+        ("1.5.b", "1.5.a", 1),
+        ("1.5.1", "1.5.b", 1),
+        // alpha dots and dashes
+        ("1.5.b-1", "1.5.b", 0),
+        ("1.5-1", "1.5.b", -1),
+        // same/similar content, differing separators
+        ("2.0", "2_0", 0),
+        ("2.0_a", "2_0.a", 0),
+        ("2.0a", "2.0.a", -1),
+        ("2___a", "2_a", 1),
+        // epoch included version comparisons
+        ("0:1.0", "0:1.0", 0),
+        ("0:1.0", "0:1.1", -1),
+        ("1:1.0", "0:1.0", 1),
+        ("1:1.0", "0:1.1", 1),
+        ("1:1.0", "2:1.1", -1),
+        // epoch + sometimes present pkgrel
+        ("1:1.0", "0:1.0-1", 1),
+        ("1:1.0-1", "0:1.1-1", 1),
+        // epoch included on one version
+        ("0:1.0", "1.0", 0),
+        ("0:1.0", "1.1", -1),
+        ("0:1.1", "1.0", 1),
+        ("1:1.0", "1.0", 1),
+        ("1:1.0", "1.1", 1),
+        ("1:1.1", "1.1", 1),
+    ];
+
+    #[test]
+    fn matches_pacman_vercmp() {
+        for &(a, b, expected) in CASES {
+            let expected = expected.cmp(&0);
+            assert_eq!(vercmp(a, b), expected, "vercmp {} {}", a, b);
+            assert_eq!(vercmp(b, a), expected.reverse(), "vercmp {} {}", b, a);
+        }
+    }
+
+    #[test]
+    fn splits_epoch_pkgver_and_pkgrel() {
+        let version = Version::new("1:2.0.r12.gabc-3");
+        assert_eq!(version.epoch, "1");
+        assert_eq!(version.pkgver, "2.0.r12.gabc");
+        assert_eq!(version.pkgrel.as_deref(), Some("3"));
+        assert_eq!(version.to_string(), "1:2.0.r12.gabc-3");
+
+        let version = Version::new("2.0");
+        assert_eq!(version.epoch, "0");
+        assert_eq!(version.pkgrel, None);
+    }
+
+    #[test]
+    fn vcs_versions() {
+        assert_eq!(vercmp("1:2.0.r12.gabc-3", "1:2.0.r13.gdef-1"), Ordering::Less);
+        assert_eq!(vercmp("1:2.0.r12.gabc-3", "2.1-1"), Ordering::Greater);
+        assert_eq!(vercmp("2.0.r9-1", "2.0.r10-1"), Ordering::Less);
+    }
+}
@@ -0,0 +1,68 @@
+//! The clone cache against real git repositories standing in for the AUR.
+
+use std::path::Path;
+use std::process::Command;
+
+use raur::aur::AurClient;
+use raur::{build, Context};
+
+fn git(dir: &Path, args: &[&str]) {
+    let status = Command::new("git")
+        .arg("-C")
+        .arg(dir)
+        .args(["-c", "user.name=raur", "-c", "user.email=raur@localhost"])
+        .args(args)
+        .status()
+        .unwrap();
+    assert!(status.success(), "git {:?} failed", args);
+}
+
+/// Commit a new PKGBUILD to the package's repository.
+fn publish(aur: &Path, pkgbase: &str, pkgbuild: &str) {
+    let repo = aur.join(format!("{}.git", pkgbase));
+    if !repo.exists() {
+        std::fs::create_dir_all(&repo).unwrap();
+        git(&repo, &["init", "--quiet"]);
+    }
+    std::fs::write(repo.join("PKGBUILD"), pkgbuild).unwrap();
+    git(&repo, &["add", "PKGBUILD"]);
+    git(&repo, &["commit", "--quiet", "-m", pkgbuild]);
+}
+
+#[test]
+fn clones_fast_forward_and_keep_local_commits() {
+    let aur = tempfile::tempdir().unwrap();
+    let cache = tempfile::tempdir().unwrap();
+    let ctx = Context::new()
+        .with_aur(AurClient::with_base_url(aur.path().display().to_string()))
+        .with_cache_dir(cache.path());
+    publish(aur.path(), "bar", "pkgver=1\n");
+
+    let clone_dir = build::sync_clone(&ctx, "bar").unwrap();
+    assert_eq!(clone_dir, cache.path().join("bar"));
+    assert_eq!(std::fs::read_to_string(clone_dir.join("PKGBUILD")).unwrap(), "pkgver=1\n");
+
+    publish(aur.path(), "bar", "pkgver=2\n");
+    build::sync_clone(&ctx, "bar").unwrap();
+    assert_eq!(std::fs::read_to_string(clone_dir.join("PKGBUILD")).unwrap(), "pkgver=2\n");
+
+    // Edited and committed during a review, nothing new on the AUR
+    std::fs::write(clone_dir.join("PKGBUILD"), "pkgver=2\noptions=(!strip)\n").unwrap();
+    git(&clone_dir, &["commit", "--quiet", "-am", "local"]);
+    build::sync_clone(&ctx, "bar").unwrap();
+    assert_eq!(std::fs::read_to_string(clone_dir.join("PKGBUILD")).unwrap(), "pkgver=2\noptions=(!strip)\n");
+
+    // The AUR moved on too: the edit is kept and the user told
+    publish(aur.path(), "bar", "pkgver=3\n");
+    let err = build::sync_clone(&ctx, "bar").unwrap_err();
+    assert_eq!(err.exit_code(), 6);
+    assert!(
+        err.to_string().starts_with(&format!(
+            "the clone of 'bar' has diverged from the AUR, merge or drop the local changes in {}: ",
+            clone_dir.display()
+        )),
+        "{}",
+        err
+    );
+    assert_eq!(std::fs::read_to_string(clone_dir.join("PKGBUILD")).unwrap(), "pkgver=2\noptions=(!strip)\n");
+}
@@ -0,0 +1,1162 @@
+//! Command sequences produced by the library operations, checked against a
+//! scripted runner and a local mock of the AUR RPC.
+
+use raur::aur::{AurClient, SearchBy};
+use raur::config::{Config, Source};
+use raur::error::Missing;
+use raur::escalation::Keepalive;
+use raur::runner::{Cmd, CmdOutput, CommandRunner, ScriptedRunner};
+use raur::ui::{self, Prompter, ScriptedPrompter};
+use raur::{build, pacman, resolve, Context, RaurError};
+use serde_json::{json, Value};
+use std::io::{Read, Write};
+use std::net::TcpListener;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Serve the AUR RPC for `packages` on a local port and return its base URL.
+fn mock_aur(packages: Vec<Value>) -> String {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let base_url = format!("http://{}", listener.local_addr().unwrap());
+
+    std::thread::spawn(move || {
+        for stream in listener.incoming() {
+            let Ok(mut stream) = stream else { continue };
+            let mut buf = vec![0; 16384];
+            let n = stream.read(&mut buf).unwrap_or(0);
+            let request = String::from_utf8_lossy(&buf[..n]).to_string();
+            let path = request.split_whitespace().nth(1).unwrap_or("/").to_string();
+
+            let url = reqwest::Url::parse(&format!("http://aur{}", path)).unwrap();
+            let args: Vec<String> = url
+                .query_pairs()
+                .filter(|(k, _)| k == "arg[]")
+                .map(|(_, v)| v.to_string())
+                .collect();
+            let results: Vec<&Value> = packages
+                .iter()
+                .filter(|pkg| args.is_empty() || args.iter().any(|arg| pkg["Name"] == arg.as_str()))
+                .collect();
+
+            let body = json!({"version": 5, "type": "multiinfo", "resultcount": results.len(), "results": results}).to_string();
+            let response = format!(
+                "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                body.len(),
+                body
+            );
+            let _ = stream.write_all(response.as_bytes());
+        }
+    });
+
+    base_url
+}
+
+fn aur_package(name: &str, version: &str, depends: &[&str]) -> Value {
+    json!({"Name": name, "PackageBase": name, "Version": version, "Description": name, "Depends": depends})
+}
+
+struct Harness {
+    runner: Arc<ScriptedRunner>,
+    cache: tempfile::TempDir,
+    /// pacman's dbpath and pacman.conf, with no repos or installed packages
+    /// until [`Harness::repo`] and [`Harness::install`]
+    db: tempfile::TempDir,
+    aur_url: String,
+}
+
+impl Harness {
+    fn new(runner: ScriptedRunner, packages: Vec<Value>) -> Self {
+        let db = tempfile::tempdir().unwrap();
+        std::fs::create_dir(db.path().join("sync")).unwrap();
+        std::fs::write(db.path().join("pacman.conf"), "[options]\n").unwrap();
+        Harness {
+            runner: Arc::new(runner),
+            cache: tempfile::tempdir().unwrap(),
+            db,
+            aur_url: mock_aur(packages),
+        }
+    }
+
+    fn context(&self, answers: &[&str]) -> Context {
+        self.configured(Config::default(), answers)
+    }
+
+    fn configured(&self, config: Config, answers: &[&str]) -> Context {
+        Context::from_config(config)
+            .with_runner(self.runner.clone())
+            .with_aur(AurClient::with_base_url(&self.aur_url))
+            .with_cache_dir(self.cache.path())
+            .with_pacman_conf(self.db.path().join("pacman.conf"))
+            .with_dbpath(self.db.path())
+            .with_prompter(ScriptedPrompter::new(answers.iter().copied()))
+    }
+
+    /// A root pacman command line, pointed at the harness's pacman.conf and
+    /// database like every transaction raur runs with them.
+    fn pacman(&self, args: &str) -> String {
+        format!(
+            "sudo pacman --config {} --dbpath {} {}",
+            self.db.path().join("pacman.conf").display(),
+            self.db.path().display(),
+            args
+        )
+    }
+
+    fn path(&self, name: &str) -> String {
+        self.cache.path().join(name).display().to_string()
+    }
+
+    /// Add a package to the local database; `extra` is appended to its desc.
+    fn install(&self, name: &str, version: &str, extra: &str) {
+        let dir = self.db.path().join("local").join(format!("{}-{}", name, version));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("desc"), format!("%NAME%\n{}\n\n%VERSION%\n{}\n\n{}", name, version, extra)).unwrap();
+    }
+
+    /// Add `line` to pacman.conf.
+    fn pacman_conf(&self, line: &str) {
+        let path = self.db.path().join("pacman.conf");
+        let conf = std::fs::read_to_string(&path).unwrap();
+        std::fs::write(&path, format!("{}{}\n", conf, line)).unwrap();
+    }
+
+    /// Add `repo` to pacman.conf and write its sync database with
+    /// `(name, version, extra desc)` entries.
+    fn repo(&self, repo: &str, packages: &[(&str, &str, &str)]) {
+        self.pacman_conf(&format!("[{}]", repo));
+        let file = std::fs::File::create(self.db.path().join("sync").join(format!("{}.db", repo))).unwrap();
+        let mut archive = tar::Builder::new(flate2::write::GzEncoder::new(file, flate2::Compression::default()));
+        for (name, version, extra) in packages {
+            let desc = format!("%NAME%\n{}\n\n%VERSION%\n{}\n\n{}", name, version, extra);
+            let mut header = tar::Header::new_gnu();
+            header.set_size(desc.len() as u64);
+            header.set_mode(0o644);
+            header.set_cksum();
+            archive
+                .append_data(&mut header, format!("{}-{}/desc", name, version), desc.as_bytes())
+                .unwrap();
+        }
+        archive.into_inner().unwrap().finish().unwrap();
+    }
+
+    /// Pretend `pkgname` was reviewed at `commit` before.
+    fn reviewed(&self, pkgname: &str, commit: &str) {
+        build::record_reviewed(&self.context(&[]), pkgname, commit).unwrap();
+    }
+}
+
+#[test]
+fn search_reads_the_sync_databases() {
+    let h = Harness::new(ScriptedRunner::new(), vec![]);
+    h.repo("core", &[("foomatic", "1-1", "%DESC%\nPrinter filters\n")]);
+    h.repo(
+        "extra",
+        &[
+            ("foo", "1.0-1", "%DESC%\nFoo\n"),
+            ("bar", "2-1", "%DESC%\nA FOO frontend\n"),
+            ("baz", "1-1", "%DESC%\nBaz\n\n%PROVIDES%\nfoo-compat=1\n"),
+            ("qux", "1-1", "%DESC%\nQux\n"),
+        ],
+    );
+    h.install("foo", "1.0-1", "");
+    let ctx = h.context(&[]);
+
+    let results = pacman::search_by(&ctx, "foo", SearchBy::NameDesc).unwrap().unwrap();
+
+    // Repo by repo, like pacman -Ss
+    let found: Vec<(&str, &str, bool)> = results
+        .iter()
+        .map(|pkg| (pkg.repo.as_str(), pkg.name.as_str(), pkg.installed))
+        .collect();
+    assert_eq!(
+        found,
+        [("core", "foomatic", false), ("extra", "bar", false), ("extra", "baz", false), ("extra", "foo", true)]
+    );
+    assert_eq!(results[3].description, "Foo");
+    assert!(h.runner.calls().is_empty());
+
+    let results = pacman::search_by(&ctx, "^fo+$", SearchBy::Name).unwrap().unwrap();
+    assert_eq!(results.len(), 1);
+    assert_eq!(results[0].name, "foo");
+
+    let err = pacman::search(&ctx, "foo(").unwrap_err();
+    assert!(err.to_string().starts_with("invalid search pattern 'foo('"));
+}
+
+#[test]
+fn search_by_depends_and_makedepends_match_alike() {
+    let h = Harness::new(ScriptedRunner::new(), vec![]);
+    h.repo(
+        "extra",
+        &[
+            ("foo", "1-1", "%PROVIDES%\nlibfoo.so=1-64\n"),
+            ("bar", "1-1", "%DEPENDS%\nfoo>=1\n"),
+            ("baz", "1-1", "%DEPENDS%\nlibfoo.so=1-64\n"),
+            ("qux", "1-1", "%DEPENDS%\nfoo>=2\n\n%MAKEDEPENDS%\nlibfoo.so\n"),
+        ],
+    );
+    h.install("baz", "1-1", "");
+    let ctx = h.context(&[]);
+    let names = |by: SearchBy, query: &str| -> Vec<(String, bool)> {
+        let results = pacman::search_by(&ctx, query, by).unwrap().unwrap();
+        results.into_iter().map(|pkg| (pkg.name, pkg.installed)).collect()
+    };
+
+    // What the package foo satisfies, like its "Required By"
+    assert_eq!(names(SearchBy::Depends, "foo"), [("bar".to_string(), false), ("baz".to_string(), true)]);
+    // A name only provided is matched by name
+    assert_eq!(names(SearchBy::Depends, "libfoo.so"), [("baz".to_string(), true)]);
+    assert_eq!(names(SearchBy::Makedepends, "libfoo.so"), [("qux".to_string(), false)]);
+    assert_eq!(names(SearchBy::Makedepends, "foo"), [("qux".to_string(), false)]);
+}
+
+#[test]
+fn search_by_provides_and_makedepends() {
+    let h = Harness::new(ScriptedRunner::new(), vec![]);
+    h.repo("core", &[("bash", "5.2-1", "%PROVIDES%\nsh\n")]);
+    h.repo(
+        "extra",
+        &[
+            ("dash", "0.5-1", "%PROVIDES%\nsh=0.5\n\n%MAKEDEPENDS%\nmeson>=1\n"),
+            ("sh", "1-1", ""),
+            ("zsh", "5.9-1", "%MAKEDEPENDS%\nmeson-python\n"),
+        ],
+    );
+    let ctx = h.context(&[]);
+    let names = |by: SearchBy, query: &str| -> Vec<String> {
+        let results = pacman::search_by(&ctx, query, by).unwrap().unwrap();
+        results.into_iter().map(|pkg| format!("{}/{}", pkg.repo, pkg.name)).collect()
+    };
+
+    assert_eq!(names(SearchBy::Provides, "sh"), ["core/bash", "extra/dash", "extra/sh"]);
+    assert_eq!(names(SearchBy::Makedepends, "meson"), ["extra/dash"]);
+    assert!(names(SearchBy::Provides, "csh").is_empty());
+    assert!(h.runner.calls().is_empty());
+}
+
+#[test]
+fn search_by_maintainer_skips_pacman() {
+    let h = Harness::new(ScriptedRunner::new(), vec![]);
+
+    assert_eq!(pacman::search_by(&h.context(&[]), "someone", SearchBy::Maintainer).unwrap(), None);
+    assert!(h.runner.calls().is_empty());
+}
+
+#[test]
+fn repo_targets_install_in_one_transaction() {
+    let h = Harness::new(ScriptedRunner::new(), vec![]);
+    h.repo("core", &[("bash", "5.2-1", "%PROVIDES%\nsh\n"), ("base-devel", "1-2", "")]);
+    h.repo(
+        "extra",
+        &[("foo", "1-1", ""), ("make", "4.4-1", "%GROUPS%\nbuild-tools\n"), ("gcc", "14-1", "%GROUPS%\nbuild-tools\n")],
+    );
+    let ctx = h.context(&[]);
+
+    let names = ["foo", "sh", "build-tools", "nowhere"].map(String::from);
+    let targets = resolve::split_targets(&ctx, &names).unwrap();
+    assert_eq!(targets.aur, ["nowhere"]);
+    let repo: Vec<String> = targets.repo.into_iter().map(|repo| repo.target).collect();
+    pacman::install(&ctx, &repo).unwrap();
+
+    assert_eq!(h.runner.calls(), [h.pacman("-S extra/foo core/bash build-tools --noconfirm")]);
+}
+
+/// Two repo packages providing `java`.
+fn java_providers(h: &Harness) {
+    h.repo(
+        "extra",
+        &[("jdk-openjdk", "23-1", "%PROVIDES%\njava\n"), ("jdk17-openjdk", "17-1", "%PROVIDES%\njava\n")],
+    );
+}
+
+#[test]
+fn several_providers_ask_the_user() {
+    let h = Harness::new(ScriptedRunner::new(), vec![]);
+    java_providers(&h);
+
+    let targets = resolve::split_targets(&h.context(&["2"]), &["java".to_string()]).unwrap();
+
+    assert_eq!(
+        targets.repo,
+        [resolve::RepoTarget {
+            name: "java".into(),
+            target: "extra/jdk17-openjdk".into(),
+        }]
+    );
+}
+
+/// Stands in for a run from cron or CI, where stdin is not a terminal.
+struct Detached;
+
+impl Prompter for Detached {
+    fn prompt(&self, question: &str) -> std::io::Result<String> {
+        panic!("asked '{}' without a terminal", question);
+    }
+
+    fn is_interactive(&self) -> bool {
+        false
+    }
+}
+
+#[test]
+fn policies_answer_for_detached_runs() {
+    let h = Harness::new(ScriptedRunner::new(), vec![]);
+    java_providers(&h);
+    let java = ["java".to_string()];
+
+    let err = resolve::split_targets(&h.context(&[]).with_prompter(Detached), &java).unwrap_err();
+    assert!(matches!(err, RaurError::NotInteractive(_)));
+    assert_eq!(err.exit_code(), 12);
+    let err = ui::confirm_removal(&h.context(&[]).with_prompter(Detached), "foo").unwrap_err();
+    assert_eq!(err.exit_code(), 12);
+
+    let mut config = Config::default();
+    config.unattended();
+    let ctx = h.configured(config, &[]).with_prompter(Detached);
+    let targets = resolve::split_targets(&ctx, &java).unwrap();
+    assert_eq!(targets.repo[0].target, "extra/jdk-openjdk");
+    assert!(ui::confirm_removal(&ctx, "foo").unwrap());
+
+    let mut config = Config::default();
+    config.set("remove", "no", Source::User).unwrap();
+    config.unattended();
+    assert!(!ui::confirm_removal(&h.configured(config, &[]).with_prompter(Detached), "foo").unwrap());
+
+    // --confirm leaves pacman's questions to the user, who is not there
+    let mut config = Config::default();
+    config.confirm_all();
+    let err = pacman::upgrade(&h.configured(config, &[]).with_prompter(Detached)).unwrap_err();
+    assert_eq!(err.exit_code(), 12);
+    assert!(!h.runner.calls().iter().any(|call| call.contains("-Syu")));
+}
+
+/// Keeps every `git clone` busy for a while and counts how many overlap.
+#[derive(Default)]
+struct SlowClones {
+    inner: ScriptedRunner,
+    running: AtomicUsize,
+    most: AtomicUsize,
+}
+
+impl CommandRunner for SlowClones {
+    fn output(&self, cmd: &Cmd) -> std::io::Result<CmdOutput> {
+        if cmd.args.first().is_some_and(|arg| arg == "clone") {
+            let now = self.running.fetch_add(1, Ordering::SeqCst) + 1;
+            self.most.fetch_max(now, Ordering::SeqCst);
+            std::thread::sleep(Duration::from_millis(100));
+            self.running.fetch_sub(1, Ordering::SeqCst);
+        }
+        self.inner.output(cmd)
+    }
+
+    fn status(&self, cmd: &Cmd) -> std::io::Result<CmdOutput> {
+        self.inner.status(cmd)
+    }
+
+    fn is_root(&self) -> bool {
+        false
+    }
+}
+
+#[tokio::test]
+async fn clones_are_fetched_in_parallel() {
+    let h = Harness::new(
+        ScriptedRunner::new(),
+        vec![aur_package("a", "1-1", &[]), aur_package("b", "1-1", &[]), aur_package("c", "1-1", &[])],
+    );
+    let runner = Arc::new(SlowClones::default());
+    let mut config = Config::default();
+    config.set("jobs", "2", Source::Cli).unwrap();
+    let ctx = h.configured(config, &[]).with_runner(runner.clone());
+
+    let clones = build::sync_clones(&ctx, &["a", "b", "c"]).unwrap();
+
+    assert_eq!(clones, [h.path("a"), h.path("b"), h.path("c")].map(std::path::PathBuf::from));
+    assert_eq!(runner.most.load(Ordering::SeqCst), 2);
+    assert_eq!(runner.inner.calls().len(), 3);
+
+    // Long info queries are split into several requests
+    let mut names: Vec<String> = (0..600).map(|i| format!("not-in-the-aur-{}", i)).collect();
+    names.push("c".into());
+    let found = ctx.aur().info(&names).await.unwrap();
+    assert_eq!(found.len(), 1);
+    assert_eq!(found[0].name, "c");
+}
+
+#[tokio::test]
+async fn aur_target_builds_dependencies_first() {
+    let runner = ScriptedRunner::new()
+        .on("makepkg --packagelist", CmdOutput::ok("/pkg/baz-1-1-any.pkg.tar.zst\n"))
+        .on("makepkg --packagelist", CmdOutput::ok("/pkg/bar-2-1-any.pkg.tar.zst\n"));
+    let h = Harness::new(
+        runner,
+        vec![aur_package("bar", "2-1", &["glibc", "git", "baz>=1"]), aur_package("baz", "1-1", &[])],
+    );
+    h.install("glibc", "2.40-1", "");
+    h.repo("extra", &[("git", "2.47-1", "")]);
+    let (bar, baz) = (h.path("bar"), h.path("baz"));
+    h.reviewed("bar", "");
+    h.reviewed("baz", "");
+    // One clone at a time keeps the command order stable
+    let mut config = Config::default();
+    config.set("jobs", "1", Source::Cli).unwrap();
+
+    let install = build::install_aur(&h.configured(config, &[]), &["bar"], false).await.unwrap();
+
+    assert_eq!(install.repo_deps, ["git"]);
+    let built: Vec<&str> = install.built.iter().map(|pkg| pkg.name.as_str()).collect();
+    assert_eq!(built, ["baz", "bar"]);
+    assert!(install.unmarked.is_empty());
+    assert_eq!(
+        h.runner.calls(),
+        [
+            format!("git clone {}/baz.git {}", h.aur_url, baz),
+            format!("git clone {}/bar.git {}", h.aur_url, bar),
+            "sudo -v".to_string(),
+            h.pacman("-S --needed --asdeps --noconfirm git"),
+            format!("git -C {} rev-parse HEAD", baz),
+            "makepkg --packagelist".to_string(),
+            "makepkg -sf --noconfirm".to_string(),
+            h.pacman("-U --noconfirm /pkg/baz-1-1-any.pkg.tar.zst"),
+            h.pacman("-D --asdeps baz"),
+            format!("git -C {} rev-parse HEAD", bar),
+            "makepkg --packagelist".to_string(),
+            "makepkg -sf --noconfirm".to_string(),
+            h.pacman("-U --noconfirm /pkg/bar-2-1-any.pkg.tar.zst"),
+        ]
+    );
+}
+
+fn split_package(name: &str, pkgbase: &str) -> Value {
+    json!({"Name": name, "PackageBase": pkgbase, "Version": "1-1", "Description": name})
+}
+
+#[tokio::test]
+async fn split_packages_share_one_build() {
+    let runner = ScriptedRunner::new().on(
+        "makepkg --packagelist",
+        CmdOutput::ok("/pkg/foo-cli-1-1-any.pkg.tar.zst\n/pkg/foo-docs-1-1-any.pkg.tar.zst\n/pkg/foo-gui-1-1-any.pkg.tar.zst\n"),
+    );
+    let h = Harness::new(runner, vec![split_package("foo-cli", "foo"), split_package("foo-docs", "foo")]);
+    let foo = h.path("foo");
+    h.reviewed("foo", "");
+
+    let install = build::install_aur(&h.context(&[]), &["foo-docs", "foo-cli"], false).await.unwrap();
+
+    let built: Vec<(&str, &str)> = install
+        .built
+        .iter()
+        .map(|pkg| (pkg.name.as_str(), pkg.pkgbase.as_str()))
+        .collect();
+    assert_eq!(built, [("foo-cli", "foo"), ("foo-docs", "foo")]);
+    assert_eq!(
+        h.runner.calls(),
+        [
+            format!("git clone {}/foo.git {}", h.aur_url, foo),
+            "sudo -v".to_string(),
+            format!("git -C {} rev-parse HEAD", foo),
+            "makepkg --packagelist".to_string(),
+            "makepkg -sf --noconfirm".to_string(),
+            h.pacman("-U --noconfirm /pkg/foo-cli-1-1-any.pkg.tar.zst /pkg/foo-docs-1-1-any.pkg.tar.zst"),
+        ]
+    );
+}
+
+#[tokio::test]
+async fn split_packages_missing_from_the_srcinfo_are_refused() {
+    let h = Harness::new(ScriptedRunner::new(), vec![split_package("foo-docs", "foo")]);
+    let foo = h.cache.path().join("foo");
+    std::fs::create_dir_all(foo.join(".git")).unwrap();
+    std::fs::write(foo.join(".SRCINFO"), "pkgbase = foo\n\tpkgver = 1\n\tpkgrel = 1\n\tarch = any\n\npkgname = foo-cli\n").unwrap();
+
+    let err = build::install_aur(&h.context(&[]), &["foo-docs"], false).await.unwrap_err();
+
+    assert_eq!(err.to_string(), "'foo-docs' is no longer built by 'foo'");
+    assert!(!h.runner.calls().iter().any(|call| call.starts_with("makepkg")));
+}
+
+#[tokio::test]
+async fn missing_dependencies_stop_before_building() {
+    let h = Harness::new(ScriptedRunner::new(), vec![aur_package("bar", "2-1", &["nowhere"])]);
+
+    let err = build::install_aur(&h.context(&[]), &["bar", "unknown"], false).await.unwrap_err();
+
+    assert_eq!(err.to_string(), "could not find 'unknown', 'nowhere' (required by 'bar')");
+    assert_eq!(err.exit_code(), 5);
+    let calls = h.runner.calls();
+    assert!(!calls.iter().any(|call| call.starts_with("git") || call.starts_with("sudo")));
+}
+
+#[tokio::test]
+async fn missing_transitive_dependencies_name_their_parent() {
+    let h = Harness::new(
+        ScriptedRunner::new(),
+        vec![aur_package("foo", "1-1", &["bar"]), aur_package("bar", "1-1", &["git", "baz>=2"])],
+    );
+    h.repo("extra", &[("git", "2.47-1", "")]);
+
+    let resolution = resolve::resolve_dependencies(&h.context(&[]), &["foo"]).await.unwrap();
+
+    assert_eq!(resolution.repo, ["git"]);
+    let bases: Vec<&str> = resolution.aur.iter().map(|base| base.pkgbase.as_str()).collect();
+    assert_eq!(bases, ["bar", "foo"]);
+    assert_eq!(
+        resolution.missing,
+        [Missing {
+            name: "baz".into(),
+            required_by: Some("bar".into()),
+        }]
+    );
+}
+
+#[tokio::test]
+async fn every_version_constraint_is_checked() {
+    let h = Harness::new(
+        ScriptedRunner::new(),
+        vec![
+            aur_package("a", "1-1", &["foo"]),
+            aur_package("b", "1-1", &["foo>=2"]),
+            aur_package("c", "1-1", &["foo>=3"]),
+            aur_package("foo", "2-1", &[]),
+        ],
+    );
+    // Enough for a, too old for b and c
+    h.install("foo", "1-1", "");
+
+    let resolution = resolve::resolve_dependencies(&h.context(&[]), &["a", "b", "c"]).await.unwrap();
+
+    let bases: Vec<&str> = resolution.aur.iter().map(|base| base.pkgbase.as_str()).collect();
+    assert_eq!(bases, ["foo", "a", "b", "c"]);
+    assert_eq!(
+        resolution.missing,
+        [Missing {
+            name: "foo>=3".into(),
+            required_by: Some("c".into()),
+        }]
+    );
+}
+
+#[tokio::test]
+async fn dependency_cycles_stop_before_cloning() {
+    let h = Harness::new(
+        ScriptedRunner::new(),
+        vec![aur_package("foo", "1-1", &["bar"]), aur_package("bar", "1-1", &["foo"])],
+    );
+
+    let err = build::install_aur(&h.context(&[]), &["foo"], false).await.unwrap_err();
+
+    assert_eq!(err.to_string(), "dependency cycle detected: bar -> foo -> bar");
+    assert_eq!(err.exit_code(), 13);
+    assert!(h.runner.calls().is_empty());
+}
+
+#[tokio::test]
+async fn install_splits_repo_and_aur_targets() {
+    let runner = ScriptedRunner::new().on("makepkg --packagelist", CmdOutput::ok("/pkg/bar-1-1-any.pkg.tar.zst\n"));
+    let h = Harness::new(runner, vec![aur_package("bar", "1-1", &[])]);
+    h.repo("extra", &[("foo", "1-1", "")]);
+    h.reviewed("bar", "");
+    let bar = h.path("bar");
+
+    let report = build::install(&h.context(&[]), &["bar".to_string(), "foo".to_string()], false).await.unwrap();
+
+    assert_eq!(report.repo[0].target, "extra/foo");
+    let built: Vec<&str> = report.aur.iter().flat_map(|aur| &aur.built).map(|pkg| pkg.name.as_str()).collect();
+    assert_eq!(built, ["bar"]);
+    assert_eq!(
+        h.runner.calls(),
+        [
+            h.pacman("-S extra/foo --noconfirm"),
+            format!("git clone {}/bar.git {}", h.aur_url, bar),
+            "sudo -v".to_string(),
+            format!("git -C {} rev-parse HEAD", bar),
+            "makepkg --packagelist".to_string(),
+            "makepkg -sf --noconfirm".to_string(),
+            h.pacman("-U --noconfirm /pkg/bar-1-1-any.pkg.tar.zst"),
+        ]
+    );
+}
+
+#[tokio::test]
+async fn menu_picks_install_from_where_they_were_listed() {
+    let runner = ScriptedRunner::new().on("makepkg --packagelist", CmdOutput::ok("/pkg/foo-2-1-any.pkg.tar.zst\n"));
+    let h = Harness::new(runner, vec![aur_package("foo", "2-1", &[])]);
+    h.repo("extra", &[("foo", "1-1", "")]);
+    h.reviewed("foo", "");
+    let ctx = h.context(&["2", "1"]);
+    let entries = resolve::menu_entries(&ctx, "foo").await.unwrap();
+    let listed: Vec<(&str, &str)> = entries.iter().map(|pkg| (pkg.repo.as_str(), pkg.name.as_str())).collect();
+    assert_eq!(listed, [("extra", "foo"), ("aur", "foo")]);
+
+    // The AUR entry builds from the AUR although the repos have a foo
+    let picked = ui::select_packages(&ctx, &entries).unwrap();
+    assert_eq!(picked, ["aur/foo"]);
+    let report = build::install(&ctx, &picked, false).await.unwrap();
+    assert!(report.repo.is_empty());
+    let built: Vec<&str> = report.aur.iter().flat_map(|aur| &aur.built).map(|pkg| pkg.name.as_str()).collect();
+    assert_eq!(built, ["foo"]);
+    assert!(!h.runner.calls().contains(&h.pacman("-S extra/foo --noconfirm")));
+
+    // The repo entry installs exactly that repo package
+    let picked = ui::select_packages(&ctx, &entries).unwrap();
+    assert_eq!(picked, ["extra/foo"]);
+    let report = build::install(&ctx, &picked, false).await.unwrap();
+    assert_eq!(report.repo[0].target, "extra/foo");
+    assert!(report.aur.is_none());
+    assert_eq!(h.runner.calls().last().unwrap(), &h.pacman("-S extra/foo --noconfirm"));
+
+    let err = build::install(&ctx, &["core/foo".to_string()], false).await.unwrap_err();
+    assert_eq!(err.to_string(), "could not find 'core/foo'");
+}
+
+#[test]
+fn remove_asks_before_running_pacman() {
+    let h = Harness::new(ScriptedRunner::new(), vec![]);
+    let packages = ["foo".to_string(), "bar".to_string()];
+
+    let err = pacman::remove_packages(&h.context(&["n"]), &packages, false).unwrap_err();
+    assert!(matches!(err, RaurError::UserAbort(_)));
+    assert!(h.runner.calls().is_empty());
+
+    let report = pacman::remove_packages(&h.context(&["y", "y"]), &packages, true).unwrap();
+    assert_eq!(report.removed, packages);
+    assert_eq!(h.runner.calls(), [h.pacman("-Rns foo --noconfirm"), h.pacman("-Rns bar --noconfirm")]);
+
+    // Packages before the refused one stay removed
+    let err = pacman::remove_packages(&h.context(&["y", "n"]), &packages, false).unwrap_err();
+    assert_eq!(err.to_string(), "removal of 'bar' aborted");
+    assert_eq!(h.runner.calls().last().unwrap(), &h.pacman("-Rs foo --noconfirm"));
+}
+
+#[test]
+fn remove_and_sync_run_pacman() {
+    let h = Harness::new(ScriptedRunner::new(), vec![]);
+    let ctx = h.context(&[]);
+
+    pacman::remove(&ctx, "foo", false).unwrap();
+    pacman::remove(&ctx, "foo", true).unwrap();
+    pacman::sync_databases(&ctx, false).unwrap();
+    pacman::sync_databases(&ctx, true).unwrap();
+
+    assert_eq!(
+        h.runner.calls(),
+        [h.pacman("-Rs foo --noconfirm"), h.pacman("-Rns foo --noconfirm"), h.pacman("-Sy"), h.pacman("-Syy")]
+    );
+}
+
+#[test]
+fn transactions_follow_the_config() {
+    let h = Harness::new(ScriptedRunner::new(), vec![]);
+    let mut config = Config::default();
+    config.set("escalation", "doas", Source::User).unwrap();
+    config.set("noconfirm", "false", Source::Env).unwrap();
+    let ctx = h.configured(config, &[]);
+
+    pacman::remove(&ctx, "foo", false).unwrap();
+    pacman::install(&ctx, &["extra/foo".to_string()]).unwrap();
+
+    let doas = |args| h.pacman(args).replacen("sudo", "doas", 1);
+    assert_eq!(h.runner.calls(), [doas("-Rs foo"), doas("-S extra/foo")]);
+}
+
+#[test]
+fn transactions_use_the_alternate_pacman_conf() {
+    let h = Harness::new(ScriptedRunner::new(), vec![]);
+    let conf = h.db.path().join("pacman.conf");
+    let mut config = Config::default();
+    config.set("pacman_conf", &conf.display().to_string(), Source::Cli).unwrap();
+    let ctx = Context::from_config(config).with_runner(h.runner.clone());
+
+    pacman::install(&ctx, &["extra/foo".to_string()]).unwrap();
+    pacman::sync_databases(&ctx, false).unwrap();
+    // The system pacman.conf and database need no flags
+    pacman::upgrade(&Context::new().with_runner(h.runner.clone())).unwrap();
+
+    assert_eq!(
+        h.runner.calls(),
+        [
+            format!("sudo pacman --config {} -S extra/foo --noconfirm", conf.display()),
+            format!("sudo pacman --config {} -Sy", conf.display()),
+            "sudo pacman -Syu --noconfirm".to_string(),
+        ]
+    );
+}
+
+#[test]
+fn failed_transactions_keep_exit_status_and_stderr() {
+    let h = Harness::new(ScriptedRunner::new(), vec![]);
+    let runner = ScriptedRunner::new().on(
+        &h.pacman("-Syu --noconfirm"),
+        CmdOutput {
+            code: Some(1),
+            stdout: String::new(),
+            stderr: "error: failed to commit transaction (conflicting files)\n".into(),
+        },
+    );
+    let h = Harness { runner: Arc::new(runner), ..h };
+
+    let err = pacman::upgrade(&h.context(&[])).unwrap_err();
+
+    assert!(matches!(err, RaurError::Pacman(_)));
+    assert_eq!(err.exit_code(), 8);
+    let failure = err.command_failure().unwrap();
+    assert_eq!(failure.code, Some(1));
+    assert_eq!(failure.stderr, "error: failed to commit transaction (conflicting files)\n");
+    assert_eq!(
+        err.to_string(),
+        format!(
+            "'{}' exited with code 1 (error: failed to commit transaction (conflicting files))",
+            h.pacman("-Syu --noconfirm")
+        )
+    );
+}
+
+#[test]
+fn failing_sudo_is_a_permission_error() {
+    let h = Harness::new(ScriptedRunner::new(), vec![]);
+    let runner = ScriptedRunner::new().on(
+        &h.pacman("-Sy"),
+        CmdOutput {
+            code: Some(1),
+            stdout: String::new(),
+            stderr: "sudo: 3 incorrect password attempts\n".into(),
+        },
+    );
+    let h = Harness { runner: Arc::new(runner), ..h };
+
+    let err = pacman::sync_databases(&h.context(&[]), false).unwrap_err();
+
+    assert!(matches!(err, RaurError::Permission(_)));
+    assert_eq!(err.exit_code(), 9);
+}
+
+#[tokio::test]
+async fn build_only_installs_what_later_builds_need() {
+    let pkgs = tempfile::tempdir().unwrap();
+    let (baz_file, bar_file) = (pkgs.path().join("baz-1-1-any.pkg.tar.zst"), pkgs.path().join("bar-1:2.0-3-x86_64.pkg.tar.zst"));
+    std::fs::write(&baz_file, "baz").unwrap();
+    std::fs::write(&bar_file, "bar").unwrap();
+    let h = Harness::new(
+        ScriptedRunner::new(),
+        vec![aur_package("bar", "1:2.0-3", &["git", "baz"]), aur_package("baz", "1-1", &[])],
+    );
+    let runner = ScriptedRunner::new()
+        .on("makepkg --packagelist", CmdOutput::ok(format!("{}\n", baz_file.display())))
+        .on("makepkg --packagelist", CmdOutput::ok(format!("{}\n", bar_file.display())))
+        .on(&h.pacman("-D --asdeps baz"), CmdOutput::failed(1));
+    let h = Harness { runner: Arc::new(runner), ..h };
+    h.repo("extra", &[("git", "2.47-1", "")]);
+    h.reviewed("bar", "");
+    h.reviewed("baz", "");
+    let output = pkgs.path().join("out");
+
+    let report = build::build_aur(&h.context(&[]), &["bar"], &output).await.unwrap();
+
+    assert_eq!(report.repo_deps, ["git"]);
+    assert_eq!(report.installed, ["baz"]);
+    // Not fatal, only reported
+    assert_eq!(report.unmarked, ["baz"]);
+    let installs: Vec<String> = h.runner.calls().into_iter().filter(|call| call.starts_with("sudo pacman")).collect();
+    assert_eq!(
+        installs,
+        [
+            h.pacman("-S --needed --asdeps --noconfirm git"),
+            h.pacman(&format!("-U --noconfirm {}", baz_file.display())),
+            h.pacman("-D --asdeps baz"),
+        ]
+    );
+
+    let entries: Vec<(&str, &str, &str, bool)> = report
+        .packages
+        .iter()
+        .map(|pkg| (pkg.name.as_str(), pkg.version.as_str(), pkg.arch.as_str(), pkg.requested))
+        .collect();
+    assert_eq!(entries, [("baz", "1-1", "any", false), ("bar", "1:2.0-3", "x86_64", true)]);
+    assert_eq!(report.packages[0].sha256, "baa5a0964d3320fbc0c6a922140453c8513ea24ab8fd0577034804a967248096");
+    assert_eq!(std::fs::read_to_string(output.join("bar-1:2.0-3-x86_64.pkg.tar.zst")).unwrap(), "bar");
+
+    let manifest: Value = serde_json::from_str(&std::fs::read_to_string(output.join(build::MANIFEST)).unwrap()).unwrap();
+    assert_eq!(manifest["kind"], "manifest");
+    assert_eq!(manifest["packages"][1]["file"], "bar-1:2.0-3-x86_64.pkg.tar.zst");
+}
+
+#[tokio::test]
+async fn failed_clone_and_build_are_reported() {
+    let h = Harness::new(ScriptedRunner::new(), vec![aur_package("bar", "1-1", &[])]);
+    let clone = format!("git clone {}/bar.git {}", h.aur_url, h.path("bar"));
+    let runner = ScriptedRunner::new().on(&clone, CmdOutput::failed(128));
+    let h = Harness { runner: Arc::new(runner), ..h };
+
+    let err = build::install_aur(&h.context(&[]), &["bar"], false).await.unwrap_err();
+    assert!(matches!(&err, RaurError::Clone { pkgbase, .. } if pkgbase == "bar"));
+    assert_eq!(err.exit_code(), 6);
+
+    let runner = ScriptedRunner::new()
+        .on("makepkg --packagelist", CmdOutput::ok("/pkg/bar-1-1-any.pkg.tar.zst\n"))
+        .on("makepkg -sf --noconfirm", CmdOutput::failed(4));
+    let h = Harness { runner: Arc::new(runner), ..h };
+    h.reviewed("bar", "");
+
+    let err = build::install_aur(&h.context(&[]), &["bar"], false).await.unwrap_err();
+    assert!(matches!(&err, RaurError::Build { failure, .. } if failure.code == Some(4)));
+    assert_eq!(err.exit_code(), 7);
+    assert!(!h.runner.calls().iter().any(|call| call.starts_with("sudo pacman")));
+}
+
+#[tokio::test]
+async fn builds_refuse_to_run_as_root() {
+    let h = Harness::new(ScriptedRunner::new().as_root(), vec![aur_package("bar", "1-1", &[])]);
+
+    let err = build::install_aur(&h.context(&[]), &["bar"], false).await.unwrap_err();
+
+    assert!(matches!(err, RaurError::Permission(_)));
+    assert!(h.runner.calls().is_empty());
+}
+
+#[test]
+fn keepalive_refreshes_until_dropped() {
+    let runner = ScriptedRunner::new().on("sudo -v", CmdOutput::ok(""));
+    let h = Harness::new(runner, vec![]);
+    let ctx = h.context(&[]);
+
+    let keepalive = Keepalive::with_interval(&ctx, Duration::from_millis(10)).unwrap();
+    std::thread::sleep(Duration::from_millis(100));
+    drop(keepalive);
+    let calls = h.runner.calls();
+    std::thread::sleep(Duration::from_millis(50));
+
+    assert_eq!(calls[0], "sudo -v");
+    assert!(calls.len() > 2 && calls[1..].iter().all(|call| call == "sudo -n -v"));
+    assert_eq!(h.runner.calls(), calls);
+
+    let runner = ScriptedRunner::new().on("sudo -v", CmdOutput::failed(1));
+    let h = Harness::new(runner, vec![]);
+    let err = Keepalive::start(&h.context(&[])).err().unwrap();
+    assert_eq!(err.exit_code(), 9);
+
+    let h = Harness::new(ScriptedRunner::new(), vec![]);
+    let mut config = Config::default();
+    config.set("escalation", "run0", Source::Cli).unwrap();
+    drop(Keepalive::start(&h.configured(config, &[])).unwrap());
+    assert!(h.runner.calls().is_empty());
+}
+
+#[tokio::test]
+async fn upgrade_check_compares_foreign_packages() {
+    let h = Harness::new(
+        ScriptedRunner::new(),
+        vec![aur_package("bar", "1.1-1", &[]), aur_package("baz", "1-1", &[])],
+    );
+    h.repo("core", &[("glibc", "2.40-1", "")]);
+    for (name, version) in [("bar", "1.0-1"), ("baz", "1-1"), ("glibc", "2.40-1"), ("local-only", "1-1")] {
+        h.install(name, version, "");
+    }
+
+    let check = resolve::aur_upgrades(&h.context(&[])).await.unwrap();
+
+    assert_eq!(
+        check.outdated,
+        [resolve::AurUpgrade {
+            name: "bar".into(),
+            local_version: "1.0-1".into(),
+            aur_version: "1.1-1".into(),
+        }]
+    );
+    assert_eq!(check.not_in_aur, ["local-only"]);
+    assert!(h.runner.calls().is_empty());
+}
+
+#[tokio::test]
+async fn upgrade_rebuilds_outdated_aur_packages() {
+    let runner = ScriptedRunner::new().on("makepkg --packagelist", CmdOutput::ok("/pkg/bar-1.1-1-any.pkg.tar.zst\n"));
+    let h = Harness::new(
+        runner,
+        vec![aur_package("bar", "1.1-1", &[]), aur_package("baz", "1-1", &[])],
+    );
+    h.install("bar", "1.0-1", "");
+    h.install("baz", "1-1", "");
+    let bar = h.path("bar");
+    h.reviewed("bar", "");
+
+    let report = build::upgrade(&h.context(&[]), false).await.unwrap();
+
+    let outdated: Vec<&str> = report.aur.outdated.iter().map(|upgrade| upgrade.name.as_str()).collect();
+    assert_eq!(outdated, ["bar"]);
+    let built: Vec<&str> = report.installed.iter().flat_map(|aur| &aur.built).map(|pkg| pkg.name.as_str()).collect();
+    assert_eq!(built, ["bar"]);
+    assert_eq!(
+        h.runner.calls(),
+        [
+            h.pacman("-Sy"),
+            h.pacman("-Syu --noconfirm"),
+            format!("git clone {}/bar.git {}", h.aur_url, bar),
+            "sudo -v".to_string(),
+            format!("git -C {} rev-parse HEAD", bar),
+            "makepkg --packagelist".to_string(),
+            "makepkg -sf --noconfirm".to_string(),
+            h.pacman("-U --noconfirm /pkg/bar-1.1-1-any.pkg.tar.zst"),
+        ]
+    );
+}
+
+#[tokio::test]
+async fn upgrade_stops_when_pacman_fails() {
+    let h = Harness::new(ScriptedRunner::new(), vec![aur_package("bar", "1.1-1", &[])]);
+    let h = Harness { runner: Arc::new(ScriptedRunner::new().on(&h.pacman("-Syu --noconfirm"), CmdOutput::failed(1))), ..h };
+    h.install("bar", "1.0-1", "");
+
+    let err = build::upgrade(&h.context(&[]), false).await.unwrap_err();
+
+    assert_eq!(err.exit_code(), 8);
+    assert_eq!(h.runner.calls(), [h.pacman("-Sy"), h.pacman("-Syu --noconfirm")]);
+}
+
+#[tokio::test]
+async fn upgrade_check_leaves_ignored_packages_alone() {
+    let h = Harness::new(
+        ScriptedRunner::new(),
+        vec![
+            aur_package("bar", "1.1-1", &[]),
+            aur_package("baz", "2-1", &[]),
+            aur_package("qux", "2-1", &[]),
+        ],
+    );
+    h.pacman_conf("IgnorePkg = ba?");
+    h.pacman_conf("IgnoreGroup = pinned");
+    h.install("bar", "1.0-1", "");
+    h.install("qux", "1-1", "%GROUPS%\npinned\n");
+    h.install("baz", "1-1", "");
+
+    let check = resolve::aur_upgrades(&h.context(&[])).await.unwrap();
+
+    let upgrade = |name: &str, local: &str, aur: &str| resolve::AurUpgrade {
+        name: name.into(),
+        local_version: local.into(),
+        aur_version: aur.into(),
+    };
+    assert!(check.outdated.is_empty());
+    assert_eq!(
+        check.ignored,
+        [upgrade("bar", "1.0-1", "1.1-1"), upgrade("baz", "1-1", "2-1"), upgrade("qux", "1-1", "2-1")]
+    );
+}
+
+/// Checks out `files` into the clone directory when `git clone` runs.
+struct AurRepo {
+    inner: ScriptedRunner,
+    files: Vec<(&'static str, &'static str)>,
+}
+
+impl CommandRunner for AurRepo {
+    fn output(&self, cmd: &Cmd) -> std::io::Result<CmdOutput> {
+        if cmd.program == "git" && cmd.args.first().is_some_and(|arg| arg == "clone") {
+            let clone_dir = std::path::Path::new(cmd.args.last().unwrap());
+            std::fs::create_dir_all(clone_dir.join(".git"))?;
+            for (name, content) in &self.files {
+                std::fs::write(clone_dir.join(name), content)?;
+            }
+        }
+        self.inner.output(cmd)
+    }
+
+    fn status(&self, cmd: &Cmd) -> std::io::Result<CmdOutput> {
+        self.inner.status(cmd)
+    }
+
+    fn is_root(&self) -> bool {
+        false
+    }
+}
+
+/// A runner for building `bar` at commit `head`.
+fn bar_builds(h: &Harness, head: &str) -> ScriptedRunner {
+    ScriptedRunner::new()
+        .on(&format!("git -C {} rev-parse HEAD", h.path("bar")), CmdOutput::ok(format!("{}\n", head)))
+        .on("makepkg --packagelist", CmdOutput::ok("/pkg/bar-1-1-any.pkg.tar.zst\n"))
+}
+
+fn last_reviewed(h: &Harness) -> Option<String> {
+    build::last_reviewed(&h.context(&[]), "bar").unwrap()
+}
+
+#[tokio::test]
+async fn new_clones_show_their_build_files() {
+    let h = Harness::new(ScriptedRunner::new(), vec![aur_package("bar", "1-1", &[])]);
+    let runner = Arc::new(AurRepo {
+        inner: bar_builds(&h, "c1"),
+        files: vec![("PKGBUILD", "pkgname=bar\n"), ("bar.install", "post_install() { :; }\n"), ("README", "")],
+    });
+    // Edit, then build
+    let ctx = h.context(&["e", "y"]).with_runner(runner.clone());
+
+    build::install_aur(&ctx, &["bar"], false).await.unwrap();
+
+    let bar = h.cache.path().join("bar");
+    assert_eq!(build::build_files(&bar).unwrap(), ["PKGBUILD", "bar.install"].map(std::path::PathBuf::from));
+    let calls = runner.inner.calls();
+    assert!(calls.iter().any(|call| call.ends_with(" PKGBUILD bar.install")), "{:?}", calls);
+    assert!(!calls.iter().any(|call| call.contains(" diff ")));
+    assert!(calls.contains(&"makepkg -sf --noconfirm".to_string()));
+    assert_eq!(last_reviewed(&h).as_deref(), Some("c1"));
+}
+
+#[tokio::test]
+async fn unknown_srcinfo_keys_do_not_stop_the_build() {
+    let h = Harness::new(ScriptedRunner::new(), vec![aur_package("bar", "1-1", &[])]);
+    h.reviewed("bar", "c1");
+    let srcinfo = "pkgbase = bar\n\tpkgver = 1\n\tpkgrel = 1\n\tcolour = blue\npkgname = bar\n";
+    let runner = AurRepo {
+        inner: bar_builds(&h, "c1"),
+        files: vec![("PKGBUILD", "pkgname=bar\n"), (".SRCINFO", srcinfo)],
+    };
+
+    let install = build::install_aur(&h.context(&[]).with_runner(runner), &["bar"], false).await.unwrap();
+
+    let path = h.cache.path().join("bar/.SRCINFO");
+    assert_eq!(
+        build::srcinfo_warnings(&install.built),
+        [format!("{}: line 4: unknown key 'colour' skipped", path.display())]
+    );
+}
+
+#[tokio::test]
+async fn reviewed_clones_show_the_diff_since_the_review() {
+    let h = Harness::new(ScriptedRunner::new(), vec![aur_package("bar", "1-1", &[])]);
+    let h = Harness { runner: Arc::new(bar_builds(&h, "c1")), ..h };
+    std::fs::create_dir_all(h.cache.path().join("bar/.git")).unwrap();
+    h.reviewed("bar", "c0");
+
+    build::install_aur(&h.context(&["y"]), &["bar"], false).await.unwrap();
+
+    let bar = h.path("bar");
+    let calls = h.runner.calls();
+    let diff = calls
+        .iter()
+        .position(|call| *call == format!("git -C {} --no-pager diff --color=always c0 HEAD", bar))
+        .expect("the diff is shown");
+    let makepkg = calls.iter().position(|call| call == "makepkg -sf --noconfirm").unwrap();
+    assert!(diff < makepkg);
+    assert_eq!(last_reviewed(&h).as_deref(), Some("c1"));
+
+    // Unchanged since then: nothing to show or ask
+    build::install_aur(&h.context(&[]).with_prompter(Detached), &["bar"], false).await.unwrap();
+    assert_eq!(h.runner.calls().iter().filter(|call| call.contains(" diff ")).count(), 1);
+}
+
+#[tokio::test]
+async fn aborted_review_skips_the_build() {
+    let h = Harness::new(ScriptedRunner::new(), vec![aur_package("bar", "1-1", &[])]);
+    let h = Harness { runner: Arc::new(bar_builds(&h, "c1")), ..h };
+    // An existing clone that was never reviewed
+    std::fs::create_dir_all(h.cache.path().join("bar/.git")).unwrap();
+    std::fs::write(h.cache.path().join("bar/PKGBUILD"), "pkgname=bar\n").unwrap();
+
+    let err = build::install_aur(&h.context(&["a"]), &["bar"], false).await.unwrap_err();
+
+    assert!(matches!(err, RaurError::UserAbort(_)));
+    assert_eq!(err.exit_code(), 10);
+    assert!(!h.runner.calls().iter().any(|call| call.starts_with("makepkg")));
+    assert_eq!(last_reviewed(&h), None);
+}
+
+#[tokio::test]
+async fn failed_builds_keep_the_previous_review() {
+    let h = Harness::new(ScriptedRunner::new(), vec![aur_package("bar", "1-1", &[])]);
+    let runner = bar_builds(&h, "c1").on("makepkg -sf --noconfirm", CmdOutput::failed(4));
+    let h = Harness { runner: Arc::new(runner), ..h };
+    std::fs::create_dir_all(h.cache.path().join("bar/.git")).unwrap();
+    h.reviewed("bar", "c0");
+
+    let err = build::install_aur(&h.context(&["y"]), &["bar"], false).await.unwrap_err();
+
+    assert_eq!(err.exit_code(), 7);
+    // The next build shows the same diff again
+    assert_eq!(last_reviewed(&h).as_deref(), Some("c0"));
+}
+
+#[test]
+fn clean_policies() {
+    let h = Harness::new(ScriptedRunner::new(), vec![]);
+    let runner = ScriptedRunner::new().on(&format!("git -C {} clean -ffdx --quiet", h.path("baz")), CmdOutput::failed(1));
+    let h = Harness { runner: Arc::new(runner), ..h };
+    for pkgbase in ["bar", "baz", "foo"] {
+        std::fs::create_dir_all(h.cache.path().join(pkgbase).join(".git")).unwrap();
+        h.reviewed(pkgbase, "c1");
+    }
+    std::fs::write(
+        h.cache.path().join("foo/.SRCINFO"),
+        "pkgbase = foo\n\tpkgver = 1\n\tpkgrel = 1\n\tarch = any\n\npkgname = foo-cli\n\npkgname = foo-docs\n",
+    )
+    .unwrap();
+    h.install("baz", "1-1", "");
+    h.install("foo-docs", "1-1", "");
+    let ctx = h.context(&[]);
+    let left = |dir: &str| -> Vec<String> {
+        let mut names: Vec<String> = std::fs::read_dir(h.cache.path().join(dir))
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().to_string_lossy().to_string())
+            .collect();
+        names.sort();
+        names
+    };
+
+    let report = build::clean(&ctx, build::CleanPolicy::Artifacts).unwrap();
+    assert_eq!(report.cleaned, ["bar", "foo"]);
+    assert_eq!(report.failed, ["baz"]);
+    assert_eq!(left("."), [".reviewed", "bar", "baz", "foo"]);
+
+    // foo stays for foo-docs, a split package of it
+    let report = build::clean(&ctx, build::CleanPolicy::KeepInstalled).unwrap();
+    assert_eq!(report.cleaned, ["bar"]);
+    assert_eq!(left("."), [".reviewed", "baz", "foo"]);
+    assert_eq!(left(".reviewed"), ["baz", "foo"]);
+
+    let report = build::clean(&ctx, build::CleanPolicy::All).unwrap();
+    assert_eq!(report.cleaned, ["baz", "foo"]);
+    assert!(left(".").is_empty());
+}
+
+#[tokio::test]
+async fn info_combines_repo_and_local_records() {
+    let h = Harness::new(ScriptedRunner::new(), vec![]);
+    h.repo(
+        "extra",
+        &[(
+            "foo",
+            "2.0-1",
+            "%LICENSE%\nMIT\nGPL\n\n%DEPENDS%\nglibc\n\n%OPTDEPENDS%\nbar: for bar support\nbaz: for baz\n\n%CSIZE%\n2048\n",
+        )],
+    );
+    h.install("foo", "1.9-1", "%SIZE%\n1572864\n\n%REASON%\n1\n");
+
+    let info = resolve::package_info(&h.context(&[]), &["foo".to_string()]).await.unwrap();
+
+    let repo = info[0].repo.as_ref().unwrap();
+    assert_eq!(repo.repository.as_deref(), Some("extra"));
+    assert_eq!(repo.licenses, ["MIT", "GPL"]);
+    assert_eq!(repo.optional_deps, ["bar: for bar support", "baz: for baz"]);
+    assert!(repo.conflicts.is_empty());
+    assert_eq!(repo.download_size.as_deref(), Some("2.00 KiB"));
+    assert_eq!(info[0].installed_version(), Some("1.9-1"));
+    let local = info[0].local.as_ref().unwrap();
+    assert_eq!(local.installed_size, "1.50 MiB");
+    assert_eq!(local.install_reason.as_deref(), Some("Installed as a dependency for another package"));
+    // The AUR is only asked for names the repos do not know
+    assert!(info[0].aur.is_none());
+}
+
+#[tokio::test]
+async fn info_falls_back_to_the_aur() {
+    let h = Harness::new(ScriptedRunner::new(), vec![aur_package("bar", "1-1", &["glibc"])]);
+    let ctx = h.context(&[]);
+
+    let err = resolve::package_info(&ctx, &["bar".to_string(), "nowhere".to_string()]).await.unwrap_err();
+    assert!(matches!(&err, RaurError::NotFound(missing) if missing.len() == 1 && missing[0].name == "nowhere"));
+
+    let info = resolve::package_info(&ctx, &["bar".to_string()]).await.unwrap();
+    let aur = info[0].aur.as_ref().unwrap();
+    assert_eq!(aur.version, "1-1");
+    assert_eq!(aur.depends, ["glibc"]);
+    assert_eq!(info[0].installed_version(), None);
+}
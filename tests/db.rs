@@ -0,0 +1,104 @@
+//! Reading pacman's databases against the files in `tests/fixtures/db`.
+
+use std::path::{Path, PathBuf};
+
+use raur::db::{InstallReason, LocalDb, SyncDbs, SyncPackage};
+use raur::RaurError;
+
+fn fixture() -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/db")
+}
+
+#[test]
+fn local_packages_read_from_their_desc() {
+    let local = LocalDb::open(&fixture()).unwrap();
+
+    // half-removed has no desc and is skipped
+    let names: Vec<&str> = local.packages().map(|pkg| pkg.name.as_str()).collect();
+    assert_eq!(names, ["bash", "jre-openjdk", "yay-bin"]);
+
+    let bash = local.package("bash").unwrap();
+    assert_eq!(bash.version, "5.2.037-1");
+    assert_eq!(bash.base.as_deref(), Some("bash"));
+    assert_eq!(bash.reason, InstallReason::Explicit);
+    assert_eq!(bash.size, 9403658);
+    assert_eq!(bash.install_date, Some(1733407052));
+    assert_eq!(bash.validation, ["pgp"]);
+    assert_eq!(bash.depends, ["readline", "libreadline.so=8-64", "glibc", "ncurses"]);
+    assert_eq!(bash.optdepends, ["bash-completion: for tab completion"]);
+
+    let jre = local.package("jre-openjdk").unwrap();
+    assert_eq!(jre.base.as_deref(), Some("java-openjdk"));
+    assert_eq!(jre.reason, InstallReason::Dependency);
+    assert_eq!(local.package("yay-bin").unwrap().version, "1:12.4.2-1");
+}
+
+#[test]
+fn dependencies_are_satisfied_by_names_and_provides() {
+    let local = LocalDb::open(&fixture()).unwrap();
+    let satisfier = |dep: &str| local.satisfier(dep).map(|pkg| pkg.name.as_str());
+
+    assert_eq!(satisfier("sh"), Some("bash"));
+    assert_eq!(satisfier("bash>=5.2"), Some("bash"));
+    assert_eq!(satisfier("bash>5.3"), None);
+    assert_eq!(satisfier("java-runtime>=17"), Some("jre-openjdk"));
+    assert_eq!(satisfier("yay"), Some("yay-bin"));
+    // The epoch outranks any pkgver
+    assert_eq!(satisfier("yay-bin>13"), Some("yay-bin"));
+    assert_eq!(satisfier("git"), None);
+}
+
+#[test]
+fn missing_databases_are_config_errors() {
+    let dir = tempfile::tempdir().unwrap();
+    // A fresh root without a local directory has nothing installed
+    assert_eq!(LocalDb::open(dir.path()).unwrap().packages().count(), 0);
+
+    let err = LocalDb::open(&dir.path().join("nowhere")).unwrap_err();
+    assert!(matches!(err, RaurError::Config(_)));
+    assert_eq!(err.exit_code(), 11);
+}
+
+#[test]
+fn sync_databases_read_gzip_and_zstd_archives() {
+    let dbs = SyncDbs::open(&fixture()).unwrap();
+
+    let repos: Vec<&str> = dbs.dbs().iter().map(|db| db.name.as_str()).collect();
+    assert_eq!(repos, ["core", "empty", "extra"]);
+
+    let bash = dbs.package("bash").unwrap();
+    assert_eq!((bash.repo.as_str(), bash.version.as_str()), ("core", "5.2.037-1"));
+    assert_eq!(bash.filename, "bash-5.2.037-1-x86_64.pkg.tar.zst");
+    assert_eq!((bash.download_size, bash.size), (1900000, 9403658));
+    assert_eq!(bash.optdepends, ["bash-completion: for tab completion"]);
+
+    // extra.db is zstd compressed and has the old separate depends files
+    let git = dbs.package("git").unwrap();
+    assert_eq!(git.repo, "extra");
+    assert_eq!(git.depends, ["curl", "perl"]);
+    assert_eq!(git.provides, ["git-core"]);
+
+    // Repos are listed in a given order, e.g. from pacman.conf
+    let dbs = SyncDbs::open_repos(&fixture(), &["extra", "core"]).unwrap();
+    assert_eq!(dbs.package("bash").unwrap().version, "5.3-1");
+    let err = SyncDbs::open_repos(&fixture(), &["multilib"]).unwrap_err();
+    assert_eq!(err.exit_code(), 11);
+}
+
+#[test]
+fn sync_lookups_by_name_provides_group_and_regex() {
+    let dbs = SyncDbs::open(&fixture()).unwrap();
+    let names = |pkgs: Vec<&SyncPackage>| -> Vec<String> { pkgs.iter().map(|pkg| pkg.name.clone()).collect() };
+
+    assert_eq!(names(dbs.providers("java-environment")), ["jdk-openjdk", "jdk17-openjdk"]);
+    assert_eq!(names(dbs.group("base-devel")), ["make", "git"]);
+    assert_eq!(dbs.satisfier("java-environment>=21").unwrap().name, "jdk-openjdk");
+    assert_eq!(dbs.satisfier("java-environment<21").unwrap().name, "jdk17-openjdk");
+    assert_eq!(dbs.satisfier("sh").unwrap().name, "bash");
+    assert!(dbs.satisfier("glibc>3").is_none());
+
+    assert_eq!(names(dbs.search("^j.*openjdk$", false).unwrap()), ["jdk-openjdk", "jdk17-openjdk"]);
+    assert_eq!(names(dbs.search("C LIBRARY", true).unwrap()), ["glibc"]);
+    assert_eq!(names(dbs.search("git-core", true).unwrap()), ["git"]);
+    assert!(dbs.search("[", false).is_err());
+}
@@ -0,0 +1,111 @@
+//! pacman.conf parsing against the files in `tests/fixtures/pacman`, and
+//! the context reading its paths and repos from it.
+
+use std::path::{Path, PathBuf};
+
+use raur::pacman_conf::{PacmanConf, Repo};
+use raur::{Context, RaurError};
+
+fn fixture(name: &str) -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures").join(name)
+}
+
+#[test]
+fn options_repos_and_includes() {
+    let conf = PacmanConf::load(&fixture("pacman/pacman.conf")).unwrap();
+
+    assert_eq!(conf.db_path, Some(PathBuf::from("/srv/pacman/")));
+    assert_eq!(conf.cache_dirs, [PathBuf::from("/var/cache/pacman/pkg/"), PathBuf::from("/srv/pkg/")]);
+    assert_eq!(conf.architecture, ["auto"]);
+    // Repeated lines add up, included files in name order
+    assert_eq!(conf.ignore_pkg, ["linux", "linux-headers", "nvidia*", "zfs-dkms"]);
+    assert_eq!(conf.ignore_group, ["kde-applications"]);
+
+    let mirror = |host: &str| format!("https://{}.example.org/$repo/os/$arch", host);
+    assert_eq!(
+        conf.repos,
+        [
+            Repo { name: "core".into(), servers: vec![mirror("mirror")] },
+            // A glob in a directory, in path order
+            Repo { name: "extra".into(), servers: vec![mirror("eu"), mirror("us")] },
+            Repo { name: "custom".into(), servers: vec!["file:///home/custompkgs".into()] },
+        ]
+    );
+
+    assert!(conf.is_ignored("linux", &[]));
+    assert!(conf.is_ignored("nvidia-dkms", &[]));
+    assert!(conf.is_ignored("kdenlive", &["kde-applications".into()]));
+    assert!(!conf.is_ignored("linux-lts", &["kde-frameworks".into()]));
+}
+
+#[test]
+fn pacman_conf_errors_name_file_and_line() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("pacman.conf");
+    let cases = [
+        ("# comment\nIgnorePkg = foo\n", 2, "directive 'IgnorePkg' is not in a section".to_string()),
+        ("[options]\n\n[local]\n", 3, "'local' is reserved and cannot be used as a repo name".to_string()),
+        ("[options]\n[core\n", 2, "invalid section '[core'".to_string()),
+        ("[]\n", 1, "invalid section '[]'".to_string()),
+        (
+            &format!("[options]\nInclude = {}\n", path.display()),
+            2,
+            format!("'{}' is included too deeply", path.display()),
+        ),
+    ];
+
+    for (text, line, message) in cases {
+        std::fs::write(&path, text).unwrap();
+
+        let err = PacmanConf::load(&path).unwrap_err();
+
+        assert!(matches!(&err, RaurError::PacmanConf { line: l, .. } if *l == line), "{}", err);
+        assert_eq!(err.to_string(), format!("{}: line {}: {}", path.display(), line, message));
+        assert_eq!(err.exit_code(), 11);
+    }
+
+    let err = PacmanConf::load(&dir.path().join("missing.conf")).unwrap_err();
+    assert!(matches!(err, RaurError::Config(_)));
+    assert_eq!(err.exit_code(), 11);
+}
+
+#[test]
+fn includes_are_not_relative_to_the_including_file() {
+    let dir = tempfile::tempdir().unwrap();
+    std::fs::write(dir.path().join("ignore.conf"), "IgnorePkg = foo\n").unwrap();
+    let conf = dir.path().join("pacman.conf");
+    let load = |include: &str| {
+        std::fs::write(&conf, format!("[options]\nInclude = {}\n", include)).unwrap();
+        PacmanConf::load(&conf).unwrap().ignore_pkg
+    };
+
+    // Like pacman: relative to the working directory, where it is missing,
+    // and a missing file includes nothing
+    assert!(load("ignore.conf").is_empty());
+    assert!(load("ignore*.conf").is_empty());
+    assert_eq!(load(&dir.path().join("ignore.conf").display().to_string()), ["foo"]);
+    assert_eq!(load(&dir.path().join("ign*.conf").display().to_string()), ["foo"]);
+}
+
+#[test]
+fn context_follows_dbpath_and_repo_order() {
+    let dir = tempfile::tempdir().unwrap();
+    let conf = dir.path().join("pacman.conf");
+    std::fs::write(
+        &conf,
+        format!("[options]\nDBPath = {}\n\n[extra]\n[core]\n", fixture("db").display()),
+    )
+    .unwrap();
+
+    let ctx = Context::new().with_pacman_conf(&conf);
+    assert_eq!(ctx.dbpath().unwrap(), fixture("db"));
+    let dbs = ctx.sync_dbs().unwrap();
+    let repos: Vec<&str> = dbs.dbs().iter().map(|db| db.name.as_str()).collect();
+    // Only the listed repos, in pacman.conf's order
+    assert_eq!(repos, ["extra", "core"]);
+    assert_eq!(dbs.package("bash").unwrap().version, "5.3-1");
+
+    // --dbpath wins over DBPath
+    let ctx = Context::new().with_pacman_conf(&conf).with_dbpath(dir.path());
+    assert_eq!(ctx.dbpath().unwrap(), dir.path());
+}
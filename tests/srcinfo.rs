@@ -0,0 +1,106 @@
+//! `.SRCINFO` parsing against the files in `tests/fixtures/srcinfo`.
+
+use std::path::{Path, PathBuf};
+
+use raur::srcinfo::{for_arch, ArchValue, Srcinfo};
+use raur::RaurError;
+
+fn fixture(name: &str) -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/srcinfo").join(name)
+}
+
+fn values(values: &[ArchValue], arch: &str) -> Vec<String> {
+    for_arch(values, arch).map(str::to_string).collect()
+}
+
+#[test]
+fn single_package_with_arch_specific_sources() {
+    let srcinfo = Srcinfo::from_file(&fixture("simple.SRCINFO")).unwrap();
+
+    assert_eq!(srcinfo.base.pkgbase, "yay-bin");
+    assert_eq!(srcinfo.version(), "1:12.4.2-1");
+    assert_eq!(srcinfo.packages.len(), 1);
+
+    let pkg = srcinfo.package("yay-bin").unwrap();
+    assert_eq!(pkg.fields.arch, ["x86_64", "aarch64"]);
+    assert_eq!(values(&pkg.fields.depends, "x86_64"), ["pacman>6.1", "git", "glibc>=2.39"]);
+    assert_eq!(values(&pkg.fields.depends, "aarch64"), ["pacman>6.1", "git"]);
+    assert_eq!(values(&pkg.fields.optdepends, "x86_64"), ["sudo: privilege elevation", "doas: privilege elevation"]);
+
+    let base = &srcinfo.base;
+    assert_eq!(
+        values(&base.source, "aarch64"),
+        [
+            "LICENSE::https://raw.githubusercontent.com/Jguer/yay/v12.4.2/LICENSE",
+            "https://github.com/Jguer/yay/releases/download/v12.4.2/yay_12.4.2_aarch64.tar.gz",
+        ]
+    );
+    let sha256 = &base.checksums["sha256sums"];
+    assert_eq!(values(sha256, "x86_64").len(), 2);
+    assert_eq!(sha256[0].value, "SKIP");
+    assert_eq!(base.validpgpkeys, ["0F6D36E1B1B8D8FBD4A4AD0D93B6C2A6EFF8B7A5"]);
+    assert_eq!(srcinfo.build_dependencies("x86_64"), ["pacman>6.1", "git", "glibc>=2.39", "tar"]);
+}
+
+#[test]
+fn split_packages_override_whole_keys() {
+    let srcinfo = Srcinfo::from_file(&fixture("split.SRCINFO")).unwrap();
+
+    assert_eq!(srcinfo.version(), "6.9.1-2.1");
+    let names: Vec<&str> = srcinfo.packages.iter().map(|pkg| pkg.pkgname.as_str()).collect();
+    assert_eq!(names, ["linux-custom", "linux-custom-headers", "linux-custom-docs"]);
+
+    let kernel = srcinfo.package("linux-custom").unwrap();
+    assert_eq!(kernel.fields.pkgdesc.as_deref(), Some("The Linux kernel and modules"));
+    assert_eq!(values(&kernel.fields.depends, "x86_64"), ["x86-firmware", "coreutils", "kmod"]);
+    assert_eq!(kernel.fields.options, ["!strip"]);
+
+    // An empty value clears the plain key, the arch specific one is separate
+    let headers = srcinfo.package("linux-custom-headers").unwrap();
+    assert_eq!(values(&headers.fields.depends, "x86_64"), ["x86-firmware"]);
+    assert_eq!(values(&headers.fields.provides, "x86_64"), ["linux-headers=6.9.1"]);
+    assert!(headers.fields.optdepends.is_empty());
+
+    let docs = srcinfo.package("linux-custom-docs").unwrap();
+    assert_eq!(docs.fields.pkgdesc.as_deref(), Some("Custom kernel"));
+    assert_eq!(docs.fields.url.as_deref(), Some("https://www.kernel.org/"));
+    assert_eq!(values(&docs.fields.depends, "x86_64"), ["coreutils", "x86-firmware"]);
+
+    assert_eq!(
+        srcinfo.build_dependencies("x86_64"),
+        ["x86-firmware", "coreutils", "kmod", "bc", "cpio", "python"]
+    );
+}
+
+/// Every file in `invalid/` starts with `# error: <expected error>`.
+#[test]
+fn invalid_files_report_the_offending_line() {
+    let mut checked = 0;
+    for entry in std::fs::read_dir(fixture("invalid")).unwrap() {
+        let path = entry.unwrap().path();
+        let text = std::fs::read_to_string(&path).unwrap();
+        let expected = text.lines().next().and_then(|line| line.strip_prefix("# error: ")).unwrap();
+
+        let err = Srcinfo::parse(&text).unwrap_err();
+        assert_eq!(err.to_string(), expected, "{}", path.display());
+        checked += 1;
+    }
+    assert!(checked >= 10);
+
+    let path = fixture("invalid/empty-arch-suffix.SRCINFO");
+    let err = Srcinfo::from_file(&path).unwrap_err();
+    assert!(matches!(&err, RaurError::Srcinfo { error, .. } if error.line == 5));
+    assert_eq!(err.to_string(), format!("{}: line 5: 'source_' has an empty architecture suffix", path.display()));
+}
+
+#[test]
+fn unknown_keys_are_skipped_with_a_warning() {
+    let text = "pkgbase = foo\n\tpkgver = 1.0\n\tpkgrel = 1\n\tcolour = blue\npkgname = foo\n\tflavour_x86_64 = mild\n";
+
+    let srcinfo = Srcinfo::parse(text).unwrap();
+
+    assert_eq!(srcinfo.version(), "1.0-1");
+    assert!(srcinfo.package("foo").is_some());
+    let warnings: Vec<String> = srcinfo.warnings.iter().map(ToString::to_string).collect();
+    assert_eq!(warnings, ["line 4: unknown key 'colour' skipped", "line 6: unknown key 'flavour_x86_64' skipped"]);
+}